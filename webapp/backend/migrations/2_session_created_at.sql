@@ -0,0 +1,2 @@
+-- セッションの有効期限を判定するために発行日時を記録する
+ALTER TABLE sessions ADD COLUMN created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP;
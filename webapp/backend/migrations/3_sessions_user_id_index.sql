@@ -0,0 +1,2 @@
+-- ユーザーごとのセッションの検索・削除のために sessions テーブルにインデックスを追加
+CREATE INDEX idx_sessions_user_id ON sessions (user_id);
@@ -0,0 +1,14 @@
+-- ドライバーの勤務 (シフト) の開始・終了を記録するテーブルを追加
+CREATE TABLE IF NOT EXISTS driver_shifts (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    tow_truck_id INT NOT NULL,
+    driver_id INT NOT NULL,
+    area_id INT NOT NULL,
+    started_at DATETIME NOT NULL,
+    ended_at DATETIME,
+    -- 勤務中のシフトはレッカー車 1 台につき 1 件だけにする
+    open_tow_truck_id INT AS (IF(ended_at IS NULL, tow_truck_id, NULL)) STORED,
+    UNIQUE (open_tow_truck_id),
+    FOREIGN KEY (tow_truck_id) REFERENCES tow_trucks(id) ON DELETE CASCADE,
+    FOREIGN KEY (driver_id) REFERENCES users(id) ON DELETE CASCADE
+);
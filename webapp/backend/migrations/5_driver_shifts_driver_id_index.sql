@@ -0,0 +1,2 @@
+-- ドライバーごとのシフト履歴を開始日時順に取得するためのインデックスを追加
+CREATE INDEX idx_driver_shifts_driver_id_started_at ON driver_shifts (driver_id, started_at);
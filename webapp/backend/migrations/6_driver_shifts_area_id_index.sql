@@ -0,0 +1,2 @@
+-- エリアごとの勤務中のシフトを取得するためのインデックスを追加
+CREATE INDEX idx_driver_shifts_area_id_ended_at ON driver_shifts (area_id, ended_at);
@@ -0,0 +1,2 @@
+-- 通行止めの道路を経路探索から除外するためのフラグを追加 (重みはそのまま残す)
+ALTER TABLE edges ADD COLUMN is_closed BOOLEAN NOT NULL DEFAULT FALSE;
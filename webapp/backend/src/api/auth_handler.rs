@@ -1,9 +1,23 @@
 use crate::domains::auth_service::AuthService;
-use crate::domains::dto::auth::{LoginRequestDto, LogoutRequestDto, RegisterRequestDto};
+use crate::domains::dto::auth::{
+    ChangePasswordRequestDto, LoginRequestDto, LogoutRequestDto, ProfileImageResponseDto,
+    RegisterRequestDto, ResetPasswordRequestDto,
+};
+use crate::domains::profile_image::{
+    ProfileImageFormat, ProfileImageOptions, ResizeMode, MAX_UPLOAD_BYTES,
+};
 use crate::errors::AppError;
+use crate::models::user::Session;
 use crate::repositories::auth_repository::AuthRepositoryImpl;
-use actix_web::{web, HttpResponse};
+use crate::utils::client_ip;
+use actix_multipart::Multipart;
+use actix_web::http::header::{
+    Accept, CacheControl, CacheDirective, ETag, EntityTag, IfNoneMatch, VARY,
+};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
+use validator::Validate;
 
 #[derive(Deserialize, Debug)]
 pub struct ValidateSessionQueryParams {
@@ -32,6 +46,8 @@ pub async fn register_handler(
     service: web::Data<AuthService<AuthRepositoryImpl>>,
     req: web::Json<RegisterRequestDto>,
 ) -> Result<HttpResponse, AppError> {
+    req.validate()?;
+
     match service
         .register_user(&req.username, &req.password, &req.role, req.area_id)
         .await
@@ -43,9 +59,16 @@ pub async fn register_handler(
 
 pub async fn login_handler(
     service: web::Data<AuthService<AuthRepositoryImpl>>,
+    http_req: HttpRequest,
     req: web::Json<LoginRequestDto>,
 ) -> Result<HttpResponse, AppError> {
-    match service.login_user(&req.username, &req.password).await {
+    req.validate()?;
+
+    let client_ip = client_ip(&http_req);
+    match service
+        .login_user(&req.username, &req.password, client_ip.as_deref())
+        .await
+    {
         Ok(response) => Ok(HttpResponse::Ok().json(response)),
         Err(err) => Err(err),
     }
@@ -55,30 +78,179 @@ pub async fn logout_handler(
     service: web::Data<AuthService<AuthRepositoryImpl>>,
     req: web::Json<LogoutRequestDto>,
 ) -> Result<HttpResponse, AppError> {
+    req.validate()?;
+
     match service.logout_user(&req.session_token).await {
         Ok(_) => Ok(HttpResponse::Ok().finish()),
         Err(_) => Ok(HttpResponse::Ok().finish()),
     }
 }
 
+pub async fn change_password_handler(
+    service: web::Data<AuthService<AuthRepositoryImpl>>,
+    session: web::ReqData<Session>,
+    http_req: HttpRequest,
+    req: web::Json<ChangePasswordRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    req.validate()?;
+
+    let client_ip = client_ip(&http_req);
+    service
+        .change_password(
+            &session,
+            &req.current_password,
+            &req.new_password,
+            client_ip.as_deref(),
+        )
+        .await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+pub async fn issue_password_reset_token_handler(
+    service: web::Data<AuthService<AuthRepositoryImpl>>,
+    session: web::ReqData<Session>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let response = service
+        .issue_password_reset_token(&session, path.into_inner())
+        .await?;
+    Ok(HttpResponse::Created().json(response))
+}
+
+pub async fn reset_password_handler(
+    service: web::Data<AuthService<AuthRepositoryImpl>>,
+    req: web::Json<ResetPasswordRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    req.validate()?;
+
+    service
+        .reset_password(&req.reset_token, &req.new_password)
+        .await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+pub async fn upload_profile_image_handler(
+    service: web::Data<AuthService<AuthRepositoryImpl>>,
+    session: web::ReqData<Session>,
+    mut payload: Multipart,
+) -> Result<HttpResponse, AppError> {
+    let mut uploaded: Option<Vec<u8>> = None;
+
+    while let Some(field) = payload.next().await {
+        let mut field = field.map_err(|_| AppError::BadRequest)?;
+        let is_image_field = field.name() == "image";
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk.map_err(|_| AppError::BadRequest)?;
+            if !is_image_field {
+                continue;
+            }
+            if bytes.len() + chunk.len() > MAX_UPLOAD_BYTES {
+                return Err(AppError::PayloadTooLarge);
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        if is_image_field {
+            uploaded = Some(bytes);
+        }
+    }
+
+    let uploaded = uploaded.ok_or(AppError::BadRequest)?;
+    let profile_image = service.update_profile_image(&session, uploaded).await?;
+
+    Ok(HttpResponse::Ok().json(ProfileImageResponseDto { profile_image }))
+}
+
 #[derive(Deserialize, Debug)]
 pub struct UserProfileImageQueryParams {
     w: Option<i32>,
     h: Option<i32>,
+    v: Option<String>,
+    mode: Option<String>,
+    circle: Option<bool>,
+    format: Option<String>,
+    quality: Option<i32>,
+}
+
+// format クエリが指定されていればそれを優先し、無ければ Accept ヘッダから出力形式を決める
+fn negotiate_profile_image_format(
+    http_req: &HttpRequest,
+    format: Option<&str>,
+) -> Result<ProfileImageFormat, AppError> {
+    if let Some(format) = format {
+        return ProfileImageFormat::from_name(format).ok_or(AppError::BadRequest);
+    }
+
+    let accept = match http_req.get_header::<Accept>() {
+        Some(accept) if !accept.is_empty() => accept,
+        _ => return Ok(ProfileImageFormat::Png),
+    };
+
+    for mime in accept.ranked() {
+        if let Some(format) = ProfileImageFormat::from_mime(mime.essence_str()) {
+            return Ok(format);
+        }
+        if mime.type_() == mime::STAR || mime.type_() == mime::IMAGE {
+            return Ok(ProfileImageFormat::Png);
+        }
+    }
+
+    Err(AppError::NotAcceptable)
 }
 
 pub async fn user_profile_image_handler(
     service: web::Data<AuthService<AuthRepositoryImpl>>,
+    http_req: HttpRequest,
     path: web::Path<i32>,
     query: web::Query<UserProfileImageQueryParams>,
 ) -> Result<HttpResponse, AppError> {
     let user_id = path.into_inner();
-    let width = query.w.unwrap_or(500);
-    let height = query.h.unwrap_or(500);
-    let profile_image_byte = service
-        .get_resized_profile_image_byte(user_id, width, height)
-        .await?;
+    let options = ProfileImageOptions {
+        width: query.w.unwrap_or(500),
+        height: query.h.unwrap_or(500),
+        mode: match query.mode.as_deref() {
+            Some(mode) => ResizeMode::from_name(mode).ok_or(AppError::BadRequest)?,
+            None => ResizeMode::Exact,
+        },
+        circle: query.circle.unwrap_or(false),
+        format: negotiate_profile_image_format(&http_req, query.format.as_deref())?,
+        quality: query.quality,
+    };
+    let variant = service.find_profile_image_variant(user_id, options).await?;
+
+    let etag = EntityTag::new_strong(variant.etag());
+    // 内容ハッシュ名の画像をそのファイル名付きの URL で取得した場合は、内容が変わらないので長期キャッシュさせる
+    let cache_control =
+        if variant.is_content_hashed() && query.v.as_deref() == Some(variant.image_name.as_str()) {
+            CacheControl(vec![
+                CacheDirective::Public,
+                CacheDirective::MaxAge(31_536_000),
+                CacheDirective::Extension("immutable".to_string(), None),
+            ])
+        } else {
+            CacheControl(vec![CacheDirective::Public, CacheDirective::NoCache])
+        };
+
+    let is_not_modified = match http_req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        None => false,
+    };
+    if is_not_modified {
+        return Ok(HttpResponse::NotModified()
+            .insert_header(ETag(etag))
+            .insert_header((VARY, "Accept"))
+            .insert_header(cache_control)
+            .finish());
+    }
+
+    let profile_image_byte = service.render_profile_image(&variant).await?;
     Ok(HttpResponse::Ok()
-        .content_type("image/png")
+        .content_type(variant.format.content_type())
+        .insert_header(ETag(etag))
+        .insert_header((VARY, "Accept"))
+        .insert_header(cache_control)
         .body(profile_image_byte))
 }
@@ -0,0 +1,101 @@
+use crate::domains::driver_service::DriverService;
+use crate::domains::dto::driver::DriverJobAction;
+use crate::errors::AppError;
+use crate::models::user::Session;
+use crate::repositories::map_repository::MapRepositoryImpl;
+use crate::repositories::order_repository::OrderRepositoryImpl;
+use crate::repositories::tow_truck_repository::TowTruckRepositoryImpl;
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+
+pub async fn get_current_job_handler(
+    service: web::Data<
+        DriverService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    session: web::ReqData<Session>,
+) -> Result<HttpResponse, AppError> {
+    match service.get_current_job(session.user_id).await {
+        Ok(Some(job)) => Ok(HttpResponse::Ok().json(job)),
+        Ok(None) => Ok(HttpResponse::NotFound().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DriverPageQuery {
+    page: Option<i32>,
+    page_size: Option<i32>,
+}
+
+pub async fn get_jobs_handler(
+    service: web::Data<
+        DriverService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    session: web::ReqData<Session>,
+    query: web::Query<DriverPageQuery>,
+) -> Result<HttpResponse, AppError> {
+    let jobs = service
+        .get_jobs(
+            session.user_id,
+            query.page.unwrap_or(0),
+            query.page_size.unwrap_or(10),
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(jobs))
+}
+
+pub async fn update_job_handler(
+    service: web::Data<
+        DriverService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    session: web::ReqData<Session>,
+    path: web::Path<(i32, DriverJobAction)>,
+) -> Result<HttpResponse, AppError> {
+    let (order_id, action) = path.into_inner();
+    let job = service
+        .update_job(session.user_id, order_id, action)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(job))
+}
+
+pub async fn start_shift_handler(
+    service: web::Data<
+        DriverService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    session: web::ReqData<Session>,
+) -> Result<HttpResponse, AppError> {
+    let shift = service.start_shift(session.user_id).await?;
+
+    Ok(HttpResponse::Created().json(shift))
+}
+
+pub async fn end_shift_handler(
+    service: web::Data<
+        DriverService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    session: web::ReqData<Session>,
+) -> Result<HttpResponse, AppError> {
+    let shift = service.end_shift(session.user_id).await?;
+
+    Ok(HttpResponse::Ok().json(shift))
+}
+
+pub async fn get_shifts_handler(
+    service: web::Data<
+        DriverService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    session: web::ReqData<Session>,
+    query: web::Query<DriverPageQuery>,
+) -> Result<HttpResponse, AppError> {
+    let shifts = service
+        .get_shifts(
+            session.user_id,
+            query.page.unwrap_or(0),
+            query.page_size.unwrap_or(10),
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(shifts))
+}
@@ -1,14 +1,20 @@
 use crate::{
-    domains::{dto::map::UpdateEdgeRequestDto, map_service::MapService},
+    domains::{
+        dto::map::{BulkUpdateEdgesRequestDto, EdgeClosureRequestDto, UpdateEdgeRequestDto},
+        map_service::MapService,
+    },
     errors::AppError,
     repositories::map_repository::MapRepositoryImpl,
 };
 use actix_web::{web, HttpResponse};
+use validator::Validate;
 
 pub async fn update_edge_handler(
     service: web::Data<MapService<MapRepositoryImpl>>,
     req: web::Json<UpdateEdgeRequestDto>,
 ) -> Result<HttpResponse, AppError> {
+    req.validate()?;
+
     match service
         .update_edge(req.node_a_id, req.node_b_id, req.weight)
         .await
@@ -17,3 +23,46 @@ pub async fn update_edge_handler(
         Err(err) => Err(err),
     }
 }
+
+pub async fn update_edges_handler(
+    service: web::Data<MapService<MapRepositoryImpl>>,
+    req: web::Json<BulkUpdateEdgesRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    req.validate()?;
+
+    let updates = req.edges.iter().map(|edge| edge.to_entity()).collect();
+    match service.update_edges(updates).await {
+        Ok(_) => Ok(HttpResponse::Ok().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn close_edge_handler(
+    service: web::Data<MapService<MapRepositoryImpl>>,
+    req: web::Json<EdgeClosureRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    req.validate()?;
+
+    match service
+        .set_edge_closed(req.node_a_id, req.node_b_id, true)
+        .await
+    {
+        Ok(_) => Ok(HttpResponse::Ok().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn reopen_edge_handler(
+    service: web::Data<MapService<MapRepositoryImpl>>,
+    req: web::Json<EdgeClosureRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    req.validate()?;
+
+    match service
+        .set_edge_closed(req.node_a_id, req.node_b_id, false)
+        .await
+    {
+        Ok(_) => Ok(HttpResponse::Ok().finish()),
+        Err(err) => Err(err),
+    }
+}
@@ -1,14 +1,28 @@
 use crate::domains::dto::order::{
-    ClientOrderRequestDto, DispatcherOrderRequestDto, UpdateOrderStatusRequestDto,
+    ClientOrderRequestDto, CreatedOrderDto, DispatcherOrderRequestDto, UpdateOrderStatusRequestDto,
 };
+use crate::domains::order_events::{OrderEvent, OrderEventScope};
 use crate::domains::order_service::OrderService;
 use crate::errors::AppError;
+use crate::models::user::Session;
 use crate::repositories::auth_repository::AuthRepositoryImpl;
 use crate::repositories::map_repository::MapRepositoryImpl;
 use crate::repositories::order_repository::OrderRepositoryImpl;
 use crate::repositories::tow_truck_repository::TowTruckRepositoryImpl;
+use actix_web::http::header;
+use actix_web::web::Bytes;
 use actix_web::{web, HttpResponse};
+use futures_util::stream;
+use log::error;
 use serde::Deserialize;
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::{interval, Interval, MissedTickBehavior};
+use validator::Validate;
+
+// プロキシにアイドル接続として切断されないよう、定期的にコメント行を送る
+const ORDER_EVENTS_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
 
 pub async fn update_order_status_handler(
     service: web::Data<
@@ -21,6 +35,8 @@ pub async fn update_order_status_handler(
     >,
     req: web::Json<UpdateOrderStatusRequestDto>,
 ) -> Result<HttpResponse, AppError> {
+    req.validate()?;
+
     match service.update_order_status(req.order_id, &req.status).await {
         Ok(_) => Ok(HttpResponse::Ok().finish()),
         Err(err) => Err(err),
@@ -44,6 +60,25 @@ pub async fn get_order_handler(
     }
 }
 
+pub async fn get_order_tracking_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    session: web::ReqData<Session>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let tracking = service
+        .track_client_order(session.user_id, path.into_inner())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(tracking))
+}
+
 #[derive(Deserialize, Debug)]
 pub struct PaginatedOrderQuery {
     page: Option<i32>,
@@ -90,17 +125,85 @@ pub async fn create_client_order_handler(
             MapRepositoryImpl,
         >,
     >,
+    session: web::ReqData<Session>,
     req: web::Json<ClientOrderRequestDto>,
 ) -> Result<HttpResponse, AppError> {
+    req.validate()?;
+
+    // 他のクライアントになりすまして依頼できないよう、依頼者はセッションから決める
     match service
-        .create_client_order(req.client_id, req.node_id, req.car_value)
+        .create_client_order(session.user_id, req.node_id, req.car_value)
         .await
     {
-        Ok(_) => Ok(HttpResponse::Created().finish()),
+        Ok(order_id) => Ok(HttpResponse::Created().json(CreatedOrderDto { order_id })),
         Err(err) => Err(err),
     }
 }
 
+#[derive(Deserialize, Debug)]
+pub struct ClientOrderQuery {
+    page: Option<i32>,
+    page_size: Option<i32>,
+    // カンマ区切りで複数指定できる (例: "pending,dispatched")
+    status: Option<String>,
+}
+
+pub async fn get_my_orders_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    session: web::ReqData<Session>,
+    query: web::Query<ClientOrderQuery>,
+) -> Result<HttpResponse, AppError> {
+    let statuses = query
+        .status
+        .as_deref()
+        .map(|status| {
+            status
+                .split(',')
+                .map(str::trim)
+                .filter(|status| !status.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let orders = service
+        .get_client_orders(
+            session.user_id,
+            statuses,
+            query.page.unwrap_or(0),
+            query.page_size.unwrap_or(10),
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(orders))
+}
+
+pub async fn repeat_order_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    session: web::ReqData<Session>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let order_id = service
+        .repeat_client_order(session.user_id, path.into_inner())
+        .await?;
+
+    Ok(HttpResponse::Created().json(CreatedOrderDto { order_id }))
+}
+
 pub async fn create_dispatcher_order_handler(
     service: web::Data<
         OrderService<
@@ -112,6 +215,8 @@ pub async fn create_dispatcher_order_handler(
     >,
     req: web::Json<DispatcherOrderRequestDto>,
 ) -> Result<HttpResponse, AppError> {
+    req.validate()?;
+
     match service
         .create_dispatcher_order(
             req.order_id,
@@ -125,3 +230,67 @@ pub async fn create_dispatcher_order_handler(
         Err(err) => Err(err),
     }
 }
+
+// オーダーの作成・配車・ステータス変更・キャンセルを Server-Sent Events で通知する
+// ディスパッチャーには担当エリアの、クライアントには自分のオーダーのイベントだけを送る
+pub async fn order_events_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    session: web::ReqData<Session>,
+) -> Result<HttpResponse, AppError> {
+    let (scope, receiver) = service.subscribe_order_events(session.user_id).await?;
+
+    let mut keep_alive = interval(ORDER_EVENTS_KEEP_ALIVE_INTERVAL);
+    keep_alive.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let events = stream::unfold(
+        (receiver, keep_alive, scope),
+        |(mut receiver, mut keep_alive, scope)| async move {
+            let chunk = next_order_event_chunk(&mut receiver, &mut keep_alive, scope).await?;
+            Some((
+                Ok::<_, Infallible>(Bytes::from(chunk)),
+                (receiver, keep_alive, scope),
+            ))
+        },
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        // nginx などのリバースプロキシにバッファリングさせず、イベントをすぐ流す
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(events))
+}
+
+// 次に送る SSE のチャンクを返す。イベントの配信が終了した場合は None
+async fn next_order_event_chunk(
+    receiver: &mut broadcast::Receiver<OrderEvent>,
+    keep_alive: &mut Interval,
+    scope: OrderEventScope,
+) -> Option<String> {
+    loop {
+        tokio::select! {
+            received = receiver.recv() => match received {
+                Ok(event) if scope.includes(&event) => match serde_json::to_string(&event) {
+                    Ok(data) => {
+                        return Some(format!("event: {}\ndata: {}\n\n", event.kind.event_name(), data))
+                    }
+                    Err(e) => error!("オーダーイベントのシリアライズに失敗しました: {:?}", e),
+                },
+                Ok(_) => {}
+                // 取りこぼしたイベントがあるので、クライアントに一覧の再取得を促す
+                Err(RecvError::Lagged(skipped)) => {
+                    return Some(format!("event: resync\ndata: {{\"skipped\":{}}}\n\n", skipped))
+                }
+                Err(RecvError::Closed) => return None,
+            },
+            _ = keep_alive.tick() => return Some(": keep-alive\n\n".to_string()),
+        }
+    }
+}
@@ -1,13 +1,34 @@
+use std::time::{Duration, Instant};
+
+use crate::domains::auth_service::AuthService;
+use crate::domains::dto::tow_truck::TowTruckDto;
+use crate::domains::tow_truck_events::TowTruckEvent;
 use crate::domains::tow_truck_service::TowTruckService;
 use crate::errors::AppError;
+use crate::models::user::Session as UserSession;
+use crate::repositories::auth_repository::AuthRepositoryImpl;
 use crate::repositories::order_repository::OrderRepositoryImpl;
 use crate::repositories::tow_truck_repository::TowTruckRepositoryImpl;
 use crate::{
     domains::dto::tow_truck::UpdateLocationRequestDto,
     repositories::map_repository::MapRepositoryImpl,
 };
-use actix_web::{web, HttpResponse};
-use serde::Deserialize;
+use actix_web::rt::time::{interval, timeout};
+use actix_web::{rt, web, HttpRequest, HttpResponse};
+use actix_ws::{CloseCode, CloseReason, Message, MessageStream, Session};
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+use validator::Validate;
+
+// クライアントが生きているかを確認する間隔と、応答がない場合に切断するまでの時間
+const POSITION_STREAM_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+const POSITION_STREAM_CLIENT_TIMEOUT: Duration = Duration::from_secs(45);
+// 送信バッファが埋まったままのクライアントは受信が追いつかないとみなして切断する
+const POSITION_STREAM_SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+type TowTruckServiceImpl =
+    TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>;
 
 #[derive(Deserialize, Debug)]
 pub struct PaginatedTowTruckQuery {
@@ -55,6 +76,8 @@ pub async fn update_location_handler(
     >,
     req: web::Json<UpdateLocationRequestDto>,
 ) -> Result<HttpResponse, AppError> {
+    req.validate()?;
+
     service
         .update_location(req.tow_truck_id, req.node_id)
         .await?;
@@ -81,3 +104,162 @@ pub async fn get_nearest_available_tow_trucks_handler(
         Err(err) => Err(err),
     }
 }
+
+#[derive(Deserialize, Debug)]
+pub struct TowTruckAreaQuery {
+    area: i32,
+}
+
+pub async fn get_on_shift_drivers_handler(
+    service: web::Data<
+        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    auth_service: web::Data<AuthService<AuthRepositoryImpl>>,
+    session: web::ReqData<UserSession>,
+    query: web::Query<TowTruckAreaQuery>,
+) -> Result<HttpResponse, AppError> {
+    auth_service.authorize_area(&session, query.area).await?;
+
+    let drivers = service.get_on_shift_drivers(query.area).await?;
+
+    Ok(HttpResponse::Ok().json(drivers))
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum TowTruckStreamMessage<'a> {
+    // 接続直後と、受信が追いつかずイベントを取りこぼした後に送る
+    Snapshot {
+        area_id: i32,
+        tow_trucks: &'a [TowTruckDto],
+    },
+    Update(&'a TowTruckEvent),
+}
+
+// 指定したエリアのレッカー車の位置とステータスの変更を WebSocket で配信する
+pub async fn tow_truck_positions_handler(
+    req: HttpRequest,
+    body: web::Payload,
+    service: web::Data<
+        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    query: web::Query<TowTruckAreaQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let area_id = query.area;
+    let (snapshot, receiver) = service.subscribe_area(area_id).await?;
+
+    let (response, session, messages) = actix_ws::handle(&req, body)?;
+    rt::spawn(stream_tow_truck_positions(
+        service.into_inner(),
+        area_id,
+        snapshot,
+        receiver,
+        session,
+        messages,
+    ));
+
+    Ok(response)
+}
+
+async fn stream_tow_truck_positions(
+    service: std::sync::Arc<TowTruckServiceImpl>,
+    area_id: i32,
+    snapshot: Vec<TowTruckDto>,
+    mut receiver: broadcast::Receiver<TowTruckEvent>,
+    mut session: Session,
+    mut messages: MessageStream,
+) {
+    let mut heartbeat = interval(POSITION_STREAM_HEARTBEAT_INTERVAL);
+    let mut last_seen = Instant::now();
+
+    let mut close_reason = send_message(
+        &mut session,
+        &TowTruckStreamMessage::Snapshot {
+            area_id,
+            tow_trucks: &snapshot,
+        },
+    )
+    .await
+    .err();
+
+    while close_reason.is_none() {
+        let result = tokio::select! {
+            received = receiver.recv() => match received {
+                Ok(event) if event.is_in_area(area_id) => {
+                    send_message(&mut session, &TowTruckStreamMessage::Update(&event)).await
+                }
+                Ok(_) => Ok(()),
+                // 取りこぼした分の差分は送らず、最新の状態を送り直す
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "tow truck position stream for area {} skipped {} events",
+                        area_id, skipped
+                    );
+                    match service.get_all_tow_trucks(0, -1, None, Some(area_id)).await {
+                        Ok(tow_trucks) => {
+                            send_message(
+                                &mut session,
+                                &TowTruckStreamMessage::Snapshot {
+                                    area_id,
+                                    tow_trucks: &tow_trucks,
+                                },
+                            )
+                            .await
+                        }
+                        Err(e) => {
+                            error!("failed to reload tow trucks of area {}: {:?}", area_id, e);
+                            Err(Some(CloseCode::Error.into()))
+                        }
+                    }
+                }
+                Err(RecvError::Closed) => Err(Some(CloseCode::Away.into())),
+            },
+            message = messages.recv() => {
+                last_seen = Instant::now();
+                match message {
+                    Some(Ok(Message::Ping(bytes))) => session.pong(&bytes).await.map_err(|_| None),
+                    Some(Ok(Message::Close(reason))) => Err(reason),
+                    Some(Ok(_)) => Ok(()),
+                    Some(Err(_)) => Err(Some(CloseCode::Protocol.into())),
+                    None => Err(None),
+                }
+            }
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > POSITION_STREAM_CLIENT_TIMEOUT {
+                    Err(Some(CloseCode::Away.into()))
+                } else {
+                    session.ping(b"").await.map_err(|_| None)
+                }
+            }
+        };
+
+        if let Err(reason) = result {
+            close_reason = Some(reason);
+        }
+    }
+
+    let _ = session.close(close_reason.flatten()).await;
+}
+
+// 送信できなかった場合は切断時に返す CloseReason を返す
+async fn send_message(
+    session: &mut Session,
+    message: &TowTruckStreamMessage<'_>,
+) -> Result<(), Option<CloseReason>> {
+    let text = match serde_json::to_string(message) {
+        Ok(text) => text,
+        Err(e) => {
+            error!("failed to serialize tow truck stream message: {:?}", e);
+            return Err(Some(CloseCode::Error.into()));
+        }
+    };
+
+    match timeout(POSITION_STREAM_SEND_TIMEOUT, session.text(text)).await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(_)) => Err(None),
+        Err(_) => Err(Some(CloseReason {
+            code: CloseCode::Policy,
+            description: Some("client is too slow to receive updates".to_string()),
+        })),
+    }
+}
@@ -0,0 +1,463 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+use std::time::Duration;
+
+use config::builder::{ConfigBuilder, DefaultState};
+use config::{Config, Environment, File};
+use serde::Deserialize;
+use thiserror::Error;
+use tracing_subscriber::EnvFilter;
+
+use crate::domains::profile_image::{PROFILE_IMAGE_CACHE_DIR, PROFILE_IMAGE_DIR};
+use crate::utils::TrustedProxies;
+
+// 設定ファイルのパスを差し替えるための環境変数
+const CONFIG_FILE_ENV: &str = "APP_CONFIG_FILE";
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+// APP__SERVER__PORT=8080 のように `__` 区切りでネストしたキーを上書きする
+const ENV_PREFIX: &str = "APP";
+const ENV_SEPARATOR: &str = "__";
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("failed to load configuration: {0}")]
+    Load(#[from] config::ConfigError),
+    #[error("invalid configuration:\n  - {}", .0.join("\n  - "))]
+    Invalid(Vec<String>),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AppConfig {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub cors: CorsConfig,
+    pub images: ImageConfig,
+    #[serde(default)]
+    pub session: SessionConfig,
+    pub rate_limit: RateLimitConfig,
+    pub logging: LoggingConfig,
+    pub background: BackgroundConfig,
+    pub eta: EtaConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerConfig {
+    pub bind_address: String,
+    pub port: u16,
+    // 未指定の場合は actix-web の既定値 (物理コア数) を使う
+    pub workers: Option<usize>,
+    // 停止要求を受けてから処理中のリクエストとバックグラウンドタスクの完了を待つ時間
+    pub shutdown_timeout_secs: u64,
+    // X-Real-IP を信頼するリバースプロキシのアドレス (IP または CIDR)。それ以外からの接続は接続元の IP を使う
+    pub trusted_proxies: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout_secs: u64,
+    // 未指定の場合はアイドル接続や古い接続を閉じない
+    pub idle_timeout_secs: Option<u64>,
+    pub max_lifetime_secs: Option<u64>,
+    // 未指定の場合はクエリ単位のタイムアウトを設定しない
+    pub statement_timeout_ms: Option<u64>,
+    // 起動時に DB へ接続できなかった場合の再試行回数と初回の待ち時間
+    pub connect_retries: u32,
+    pub connect_retry_backoff_ms: u64,
+    // 起動時に未適用のマイグレーションを適用する (false の場合は未適用があると起動しない)
+    pub migrate_on_startup: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CorsConfig {
+    // "*" を指定するとすべてのオリジンを許可する
+    pub allowed_origins: Vec<String>,
+    pub max_age_secs: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImageConfig {
+    pub profile_image_dir: String,
+    pub cache_dir: String,
+    pub cache_capacity: usize,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SessionConfig {
+    // 未指定の場合はログアウトするまでセッションを有効とする
+    pub lifetime_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateLimitConfig {
+    pub login_max_requests: u32,
+    pub register_max_requests: u32,
+    pub window_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BackgroundConfig {
+    pub session_cleanup_interval_secs: u64,
+    pub graph_cache_refresh_interval_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EtaConfig {
+    // 辺の重み 1 あたりの所要時間 (秒)
+    pub weight_unit_secs: f64,
+    // エリア ID ごとの速度係数。2.0 なら所要時間が半分になる (未指定のエリアは 1.0)
+    #[serde(default)]
+    pub area_speed_factors: HashMap<String, f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Json,
+    Text,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoggingConfig {
+    // RUST_LOG と同じ書式 (例: "info,backend::repositories=debug")。RUST_LOG が設定されていればそちらを優先する
+    pub level: String,
+    pub format: LogFormat,
+    // サービス・リポジトリ呼び出しのスパン終了時に所要時間を出力する
+    pub log_spans: bool,
+}
+
+impl ServerConfig {
+    pub fn bind_addr(&self) -> String {
+        format!("{}:{}", self.bind_address, self.port)
+    }
+
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_secs)
+    }
+}
+
+impl DatabaseConfig {
+    pub fn acquire_timeout(&self) -> Duration {
+        Duration::from_secs(self.acquire_timeout_secs)
+    }
+
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout_secs.map(Duration::from_secs)
+    }
+
+    pub fn max_lifetime(&self) -> Option<Duration> {
+        self.max_lifetime_secs.map(Duration::from_secs)
+    }
+
+    pub fn connect_retry_backoff(&self) -> Duration {
+        Duration::from_millis(self.connect_retry_backoff_ms)
+    }
+}
+
+impl SessionConfig {
+    pub fn lifetime(&self) -> Option<chrono::Duration> {
+        self.lifetime_secs
+            .map(|secs| chrono::Duration::seconds(secs as i64))
+    }
+}
+
+impl BackgroundConfig {
+    pub fn session_cleanup_interval(&self) -> Duration {
+        Duration::from_secs(self.session_cleanup_interval_secs)
+    }
+
+    pub fn graph_cache_refresh_interval(&self) -> Duration {
+        Duration::from_secs(self.graph_cache_refresh_interval_secs)
+    }
+}
+
+impl EtaConfig {
+    // validate() 済みであることを前提に、キーをエリア ID に変換する
+    pub fn speed_factors(&self) -> HashMap<i32, f64> {
+        self.area_speed_factors
+            .iter()
+            .filter_map(|(area_id, factor)| area_id.parse().ok().map(|area_id| (area_id, *factor)))
+            .collect()
+    }
+}
+
+impl RateLimitConfig {
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.window_secs)
+    }
+}
+
+impl AppConfig {
+    // 既定値 → 設定ファイル → 環境変数 の順に上書きして読み込む
+    pub fn load() -> Result<Self, ConfigError> {
+        let (config_file, required) = match env::var(CONFIG_FILE_ENV) {
+            Ok(path) => (path, true),
+            Err(_) => (DEFAULT_CONFIG_FILE.to_string(), false),
+        };
+
+        let mut builder =
+            Self::defaults()?.add_source(File::with_name(&config_file).required(required));
+
+        // 従来どおり DATABASE_URL でも接続先を指定できるようにする (APP__DATABASE__URL が優先)
+        if env::var("APP__DATABASE__URL").is_err() {
+            if let Ok(url) = env::var("DATABASE_URL") {
+                builder = builder.set_override("database.url", url)?;
+            }
+        }
+
+        let config: AppConfig = builder
+            .add_source(
+                Environment::with_prefix(ENV_PREFIX)
+                    .prefix_separator(ENV_SEPARATOR)
+                    .separator(ENV_SEPARATOR)
+                    .list_separator(",")
+                    .with_list_parse_key("cors.allowed_origins")
+                    .with_list_parse_key("server.trusted_proxies")
+                    .try_parsing(true),
+            )
+            .build()?
+            .try_deserialize()?;
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn defaults() -> Result<ConfigBuilder<DefaultState>, config::ConfigError> {
+        // ローカル開発環境はコンテナ内で 18080 番を使う
+        let default_port = if cfg!(debug_assertions) { 18080 } else { 8080 };
+
+        Config::builder()
+            .set_default("server.bind_address", "0.0.0.0")?
+            .set_default("server.port", default_port)?
+            .set_default("server.shutdown_timeout_secs", 30)?
+            .set_default("server.trusted_proxies", Vec::<String>::new())?
+            .set_default("database.url", "")?
+            .set_default("database.max_connections", 10)?
+            .set_default("database.min_connections", 0)?
+            .set_default("database.acquire_timeout_secs", 30)?
+            .set_default("database.idle_timeout_secs", 600)?
+            .set_default("database.max_lifetime_secs", 1800)?
+            .set_default("database.connect_retries", 10)?
+            .set_default("database.connect_retry_backoff_ms", 500)?
+            .set_default("database.migrate_on_startup", true)?
+            .set_default("cors.allowed_origins", Vec::<String>::new())?
+            .set_default("cors.max_age_secs", 3600)?
+            .set_default("images.profile_image_dir", PROFILE_IMAGE_DIR)?
+            .set_default("images.cache_dir", PROFILE_IMAGE_CACHE_DIR)?
+            .set_default("images.cache_capacity", 512)?
+            .set_default("rate_limit.login_max_requests", 30)?
+            .set_default("rate_limit.register_max_requests", 10)?
+            .set_default("rate_limit.window_secs", 60)?
+            .set_default("logging.level", "info")?
+            .set_default("logging.format", "json")?
+            .set_default("logging.log_spans", false)?
+            .set_default("background.session_cleanup_interval_secs", 300)?
+            .set_default("background.graph_cache_refresh_interval_secs", 300)?
+            .set_default("eta.weight_unit_secs", 1.0)
+    }
+
+    // 既定値に DB の接続先だけを与えた設定 (テスト用)
+    #[cfg(test)]
+    pub fn with_defaults() -> Self {
+        Self::defaults()
+            .and_then(|builder| builder.set_override("database.url", "mysql://localhost/test"))
+            .and_then(|builder| builder.build())
+            .and_then(|config| config.try_deserialize())
+            .unwrap()
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let mut errors = Vec::new();
+
+        if self.server.bind_address.trim().is_empty() {
+            errors.push("server.bind_address must not be empty".to_string());
+        }
+        if self.server.port == 0 {
+            errors.push("server.port must be between 1 and 65535".to_string());
+        }
+        if self.server.workers == Some(0) {
+            errors.push("server.workers must be at least 1".to_string());
+        }
+        if self.server.shutdown_timeout_secs == 0 {
+            errors.push("server.shutdown_timeout_secs must be at least 1".to_string());
+        }
+        if let Err(entry) = TrustedProxies::parse(&self.server.trusted_proxies) {
+            errors.push(format!(
+                "server.trusted_proxies contains invalid address {:?} (expected IP or CIDR)",
+                entry
+            ));
+        }
+
+        if self.database.url.trim().is_empty() {
+            errors.push("database.url must be set (or set DATABASE_URL)".to_string());
+        } else if !self.database.url.starts_with("mysql://") {
+            errors.push("database.url must start with mysql://".to_string());
+        }
+        if self.database.max_connections == 0 {
+            errors.push("database.max_connections must be at least 1".to_string());
+        }
+        if self.database.min_connections > self.database.max_connections {
+            errors.push(format!(
+                "database.min_connections ({}) must not exceed database.max_connections ({})",
+                self.database.min_connections, self.database.max_connections
+            ));
+        }
+        if self.database.acquire_timeout_secs == 0 {
+            errors.push("database.acquire_timeout_secs must be at least 1".to_string());
+        }
+        if self.database.idle_timeout_secs == Some(0) {
+            errors.push("database.idle_timeout_secs must be at least 1".to_string());
+        }
+        if self.database.max_lifetime_secs == Some(0) {
+            errors.push("database.max_lifetime_secs must be at least 1".to_string());
+        }
+        if self.database.statement_timeout_ms == Some(0) {
+            errors.push("database.statement_timeout_ms must be at least 1".to_string());
+        }
+        if self.database.connect_retry_backoff_ms == 0 {
+            errors.push("database.connect_retry_backoff_ms must be at least 1".to_string());
+        }
+
+        for origin in &self.cors.allowed_origins {
+            let valid = origin == "*"
+                || ((origin.starts_with("http://") || origin.starts_with("https://"))
+                    && !origin.ends_with('/'));
+            if !valid {
+                errors.push(format!(
+                    "cors.allowed_origins contains invalid origin {:?} (expected \"*\" or scheme://host[:port])",
+                    origin
+                ));
+            }
+        }
+
+        if !Path::new(&self.images.profile_image_dir).is_dir() {
+            errors.push(format!(
+                "images.profile_image_dir {:?} is not a directory",
+                self.images.profile_image_dir
+            ));
+        }
+        if self.images.cache_dir.trim().is_empty() {
+            errors.push("images.cache_dir must not be empty".to_string());
+        }
+        if self.images.cache_capacity == 0 {
+            errors.push("images.cache_capacity must be at least 1".to_string());
+        }
+
+        if self.session.lifetime_secs == Some(0) {
+            errors.push("session.lifetime_secs must be at least 1".to_string());
+        }
+
+        if self.rate_limit.login_max_requests == 0 {
+            errors.push("rate_limit.login_max_requests must be at least 1".to_string());
+        }
+        if self.rate_limit.register_max_requests == 0 {
+            errors.push("rate_limit.register_max_requests must be at least 1".to_string());
+        }
+        if self.rate_limit.window_secs == 0 {
+            errors.push("rate_limit.window_secs must be at least 1".to_string());
+        }
+
+        if self.background.session_cleanup_interval_secs == 0 {
+            errors.push("background.session_cleanup_interval_secs must be at least 1".to_string());
+        }
+        if self.background.graph_cache_refresh_interval_secs == 0 {
+            errors.push(
+                "background.graph_cache_refresh_interval_secs must be at least 1".to_string(),
+            );
+        }
+
+        if !(self.eta.weight_unit_secs.is_finite() && self.eta.weight_unit_secs > 0.0) {
+            errors.push("eta.weight_unit_secs must be greater than 0".to_string());
+        }
+        for (area_id, factor) in &self.eta.area_speed_factors {
+            if area_id.parse::<i32>().is_err() {
+                errors.push(format!(
+                    "eta.area_speed_factors key {:?} is not an area id",
+                    area_id
+                ));
+            }
+            if !(factor.is_finite() && *factor > 0.0) {
+                errors.push(format!(
+                    "eta.area_speed_factors.{} must be greater than 0",
+                    area_id
+                ));
+            }
+        }
+
+        if EnvFilter::try_new(&self.logging.level).is_err() {
+            errors.push(format!(
+                "logging.level {:?} is not a valid filter directive",
+                self.logging.level
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(errors))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors_of(config: &AppConfig) -> Vec<String> {
+        match config.validate() {
+            Ok(()) => Vec::new(),
+            Err(ConfigError::Invalid(errors)) => errors,
+            Err(err) => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(errors_of(&AppConfig::with_defaults()).is_empty());
+    }
+
+    #[test]
+    fn validate_reports_every_invalid_value() {
+        let mut config = AppConfig::with_defaults();
+        config.database.url = "postgres://localhost/test".to_string();
+        config.database.min_connections = 20;
+        config.cors.allowed_origins = vec!["https://example.com/".to_string()];
+        config.session.lifetime_secs = Some(0);
+        config.server.trusted_proxies = vec!["nginx".to_string()];
+
+        let errors = errors_of(&config);
+
+        assert_eq!(errors.len(), 5, "{:?}", errors);
+        assert!(errors[0].starts_with("server.trusted_proxies"));
+        assert!(errors[1].starts_with("database.url"));
+        assert!(errors[2].starts_with("database.min_connections"));
+        assert!(errors[3].starts_with("cors.allowed_origins"));
+        assert!(errors[4].starts_with("session.lifetime_secs"));
+    }
+
+    #[test]
+    fn validate_requires_database_url() {
+        let mut config = AppConfig::with_defaults();
+        config.database.url = String::new();
+
+        assert_eq!(
+            errors_of(&config),
+            vec!["database.url must be set (or set DATABASE_URL)".to_string()]
+        );
+    }
+
+    #[test]
+    fn session_lifetime_is_optional() {
+        let mut config = AppConfig::with_defaults();
+        assert_eq!(config.session.lifetime(), None);
+
+        config.session.lifetime_secs = Some(90);
+        assert_eq!(
+            config.session.lifetime(),
+            Some(chrono::Duration::seconds(90))
+        );
+    }
+}
@@ -1,19 +1,29 @@
-use std::io::Cursor;
-use image::imageops::FilterType;
-use image::ImageOutputFormat;
-use image::io::Reader as ImageReader;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Instant;
 
-use std::path::{Path, PathBuf};
-use std::process::Command;
-
-use actix_web::web::Bytes;
+use actix_web::web::{self, Bytes};
+use chrono::{DateTime, Duration, Utc};
 use log::error;
+use tracing::instrument;
 
+use crate::config::{ImageConfig, SessionConfig};
 use crate::errors::AppError;
-use crate::models::user::{Dispatcher, Session, User};
-use crate::utils::{generate_session_token, hash_password, verify_password};
+use crate::infrastructure::image_cache::ImageCache;
+use crate::infrastructure::metrics::metrics;
+use crate::models::user::{Dispatcher, PasswordResetToken, Session, User};
+use crate::utils::{
+    generate_password_reset_token, generate_session_token, hash_password, verify_password,
+};
+
+use super::dto::auth::{LoginResponseDto, PasswordResetTokenResponseDto};
+use super::login_attempt_tracker::LoginAttemptTracker;
+use super::profile_image::{self, ProfileImageOptions, ProfileImageVariant};
+
+const PASSWORD_RESET_TOKEN_LIFETIME_MINUTES: i64 = 30;
 
-use super::dto::auth::LoginResponseDto;
+// 登録 API では作成できず、`backend grant-admin <username>` でのみ付与するロール
+pub const ADMIN_ROLE: &str = "admin";
 
 pub trait AuthRepository {
     async fn create_user(&self, username: &str, password: &str, role: &str)
@@ -30,22 +40,60 @@ pub trait AuthRepository {
         &self,
         user_id: i32,
     ) -> Result<Option<String>, AppError>;
+    async fn update_profile_image_name(
+        &self,
+        user_id: i32,
+        profile_image_name: &str,
+    ) -> Result<(), AppError>;
     async fn create_session(&self, user_id: i32, session_token: &str) -> Result<(), AppError>;
     async fn delete_session(&self, session_token: &str) -> Result<(), AppError>;
     async fn find_session_by_session_token(&self, session_token: &str)
         -> Result<Session, AppError>;
+    async fn update_password(&self, user_id: i32, password: &str) -> Result<(), AppError>;
+    async fn update_role(&self, user_id: i32, role: &str) -> Result<(), AppError>;
+    async fn invalidate_sessions_by_user_id(
+        &self,
+        user_id: i32,
+        except_session_token: Option<&str>,
+    ) -> Result<(), AppError>;
+    async fn create_password_reset_token(
+        &self,
+        user_id: i32,
+        token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError>;
+    async fn find_password_reset_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<PasswordResetToken>, AppError>;
+    async fn mark_password_reset_token_used(&self, id: i32) -> Result<bool, AppError>;
+    async fn delete_stale_sessions(
+        &self,
+        created_before: Option<DateTime<Utc>>,
+    ) -> Result<u64, AppError>;
 }
 
 #[derive(Debug)]
 pub struct AuthService<T: AuthRepository + std::fmt::Debug> {
     repository: T,
+    login_attempts: LoginAttemptTracker,
+    profile_image_dir: PathBuf,
+    profile_image_cache: ImageCache,
+    session_lifetime: Option<Duration>,
 }
 
 impl<T: AuthRepository + std::fmt::Debug> AuthService<T> {
-    pub fn new(repository: T) -> Self {
-        AuthService { repository }
+    pub fn new(repository: T, images: &ImageConfig, session: &SessionConfig) -> Self {
+        AuthService {
+            repository,
+            login_attempts: LoginAttemptTracker::default(),
+            profile_image_dir: PathBuf::from(&images.profile_image_dir),
+            profile_image_cache: ImageCache::new(&images.cache_dir, images.cache_capacity),
+            session_lifetime: session.lifetime(),
+        }
     }
 
+    #[instrument(skip(self, password))]
     pub async fn register_user(
         &self,
         username: &str,
@@ -61,7 +109,7 @@ impl<T: AuthRepository + std::fmt::Debug> AuthService<T> {
             return Err(AppError::Conflict);
         }
 
-        let hashed_password = hash_password(password).unwrap();
+        let hashed_password = hash_password(password)?;
 
         self.repository
             .create_user(username, &hashed_password, role)
@@ -76,14 +124,13 @@ impl<T: AuthRepository + std::fmt::Debug> AuthService<T> {
                     .await?;
                 match user.role.as_str() {
                     "dispatcher" => {
-                        self.repository
-                            .create_dispatcher(user.id, area.unwrap())
-                            .await?;
+                        let area_id = area.ok_or(AppError::BadRequest)?;
+                        self.repository.create_dispatcher(user.id, area_id).await?;
                         let dispatcher = self
                             .repository
                             .find_dispatcher_by_user_id(user.id)
                             .await?
-                            .unwrap();
+                            .ok_or(AppError::InternalServerError)?;
                         Ok(LoginResponseDto {
                             user_id: user.id,
                             username: user.username,
@@ -107,17 +154,24 @@ impl<T: AuthRepository + std::fmt::Debug> AuthService<T> {
         }
     }
 
+    #[instrument(skip(self, password))]
     pub async fn login_user(
         &self,
         username: &str,
         password: &str,
+        client_ip: Option<&str>,
     ) -> Result<LoginResponseDto, AppError> {
+        // ロック中はパスワード検証（argon2）を行わずに弾く
+        self.login_attempts.check(username, client_ip)?;
+
         match self.repository.find_user_by_username(username).await? {
             Some(user) => {
-                let is_password_valid = verify_password(&user.password, password).unwrap();
+                let is_password_valid = verify_password(&user.password, password)?;
                 if !is_password_valid {
+                    self.login_attempts.record_failure(username, client_ip);
                     return Err(AppError::Unauthorized);
                 }
+                self.login_attempts.record_success(username, client_ip);
 
                 let session_token = generate_session_token();
                 self.repository
@@ -148,63 +202,98 @@ impl<T: AuthRepository + std::fmt::Debug> AuthService<T> {
                     }),
                 }
             }
-            None => Err(AppError::Unauthorized),
+            None => {
+                self.login_attempts.record_failure(username, client_ip);
+                Err(AppError::Unauthorized)
+            }
         }
     }
 
+    #[instrument(skip_all)]
     pub async fn logout_user(&self, session_token: &str) -> Result<(), AppError> {
         self.repository.delete_session(session_token).await?;
         Ok(())
     }
 
-    pub async fn get_resized_profile_image_byte(
+    pub async fn find_profile_image_variant(
         &self,
         user_id: i32,
-        width: i32,
-        height: i32,
-    ) -> Result<Bytes, AppError> {
-        let profile_image_name = match self
+        options: ProfileImageOptions,
+    ) -> Result<ProfileImageVariant, AppError> {
+        let (width, height) = profile_image::validate_size(options.width, options.height)?;
+        let quality = profile_image::normalize_quality(options.format, options.quality)?;
+
+        let image_name = match self
             .repository
             .find_profile_image_name_by_user_id(user_id)
             .await
         {
             Ok(Some(name)) => name,
             Ok(None) => return Err(AppError::NotFound),
-            Err(_) => return Err(AppError::NotFound),
+            Err(err) => return Err(err),
         };
 
-        let path: PathBuf =
-            Path::new(&format!("images/user_profile/{}", profile_image_name)).to_path_buf();
+        Ok(ProfileImageVariant {
+            image_name,
+            width,
+            height,
+            mode: options.mode,
+            circle: options.circle,
+            format: options.format,
+            quality,
+        })
+    }
 
-        // 指定されたパスから画像を読み込む
-        let img = ImageReader::open(&path)
-        .map_err(|e| {
-            error!("画像の読み込みに失敗しました: {:?}", e);
-            AppError::InternalServerError
-        })?
-        .decode()
-        .map_err(|e| {
-            error!("画像のデコードに失敗しました: {:?}", e);
-            AppError::InternalServerError
-        })?;
-
-        // 画像を指定された寸法にリサイズする
-        let resized = img.resize_exact(
-            width.try_into().map_err(|_| AppError::BadRequest)?,
-            height.try_into().map_err(|_| AppError::BadRequest)?,
-            FilterType::Lanczos3
-        );        
-
-        // リサイズした画像をPNG形式でバッファに書き込む
-        let mut buffer = Vec::new();
-        resized.write_to(&mut Cursor::new(&mut buffer), ImageOutputFormat::Png)
+    #[instrument(skip(self))]
+    pub async fn render_profile_image(
+        &self,
+        variant: &ProfileImageVariant,
+    ) -> Result<Bytes, AppError> {
+        let path = self.profile_image_dir.join(&variant.image_name);
+        let owned_variant = variant.clone();
+
+        self.profile_image_cache
+            .get_or_generate(&variant.cache_key(), move || {
+                let started_at = Instant::now();
+                let rendered = profile_image::render(&path, &owned_variant);
+                metrics().observe_profile_image_render(
+                    owned_variant.format.extension(),
+                    started_at.elapsed(),
+                );
+                rendered
+            })
+            .await
+    }
+
+    #[instrument(skip_all, fields(user_id = session.user_id))]
+    pub async fn update_profile_image(
+        &self,
+        session: &Session,
+        uploaded: Vec<u8>,
+    ) -> Result<String, AppError> {
+        let profile_image_dir = self.profile_image_dir.clone();
+        let file_name = web::block(move || -> Result<String, AppError> {
+            let normalized = profile_image::normalize_upload(&uploaded)?;
+            let path = profile_image_dir.join(&normalized.file_name);
+            if !path.exists() {
+                fs::write(&path, &normalized.bytes).map_err(|e| {
+                    error!("プロフィール画像の保存に失敗しました: {:?}", e);
+                    AppError::InternalServerError
+                })?;
+            }
+            Ok(normalized.file_name)
+        })
+        .await
         .map_err(|e| {
-            error!("画像の書き込みに失敗しました: {:?}", e);
+            error!("画像処理スレッドの実行に失敗しました: {:?}", e);
             AppError::InternalServerError
-        })?;
+        })??;
+
+        self.repository
+            .update_profile_image_name(session.user_id, &file_name)
+            .await?;
 
-        // バッファからBytesを生成して返す
-        Ok(Bytes::from(buffer))
+        Ok(file_name)
     }
 
     pub async fn validate_session(&self, session_token: &str) -> Result<bool, AppError> {
@@ -213,6 +302,499 @@ impl<T: AuthRepository + std::fmt::Debug> AuthService<T> {
             .find_session_by_session_token(session_token)
             .await?;
 
-        Ok(session.is_valid)
+        Ok(session.is_valid && !self.is_session_expired(&session))
+    }
+
+    #[instrument(skip_all)]
+    pub async fn find_valid_session(&self, session_token: &str) -> Result<Session, AppError> {
+        let session = self
+            .repository
+            .find_session_by_session_token(session_token)
+            .await?;
+
+        if !session.is_valid || self.is_session_expired(&session) {
+            return Err(AppError::Unauthorized);
+        }
+
+        Ok(session)
+    }
+
+    // 無効化済み・期限切れのセッションを削除し、削除した件数を返す
+    #[instrument(skip(self))]
+    pub async fn cleanup_sessions(&self) -> Result<u64, AppError> {
+        let created_before = self.session_lifetime.map(|lifetime| Utc::now() - lifetime);
+        self.repository.delete_stale_sessions(created_before).await
+    }
+
+    fn is_session_expired(&self, session: &Session) -> bool {
+        match self.session_lifetime {
+            Some(lifetime) => session.created_at + lifetime <= Utc::now(),
+            None => false,
+        }
+    }
+
+    #[instrument(skip_all, fields(user_id = session.user_id))]
+    pub async fn change_password(
+        &self,
+        session: &Session,
+        current_password: &str,
+        new_password: &str,
+        client_ip: Option<&str>,
+    ) -> Result<(), AppError> {
+        let user = match self.repository.find_user_by_id(session.user_id).await? {
+            Some(user) => user,
+            None => return Err(AppError::Unauthorized),
+        };
+
+        // セッションを持っていても現在のパスワードを総当たりできないよう、ログインと同じ失敗回数で制限する
+        self.login_attempts.check(&user.username, client_ip)?;
+        if !verify_password(&user.password, current_password)? {
+            self.login_attempts
+                .record_failure(&user.username, client_ip);
+            return Err(AppError::Unauthorized);
+        }
+        self.login_attempts
+            .record_success(&user.username, client_ip);
+
+        let hashed_password = hash_password(new_password)?;
+        self.repository
+            .update_password(user.id, &hashed_password)
+            .await?;
+
+        // 現在のセッションは残し、他の端末のセッションを無効化する
+        self.repository
+            .invalidate_sessions_by_user_id(user.id, Some(&session.session_token))
+            .await?;
+
+        Ok(())
+    }
+
+    // 既存のユーザーに管理者ロールを付与する (サーバー上で grant-admin コマンドから実行する)
+    #[instrument(skip(self))]
+    pub async fn grant_admin(&self, username: &str) -> Result<(), AppError> {
+        let user = self
+            .repository
+            .find_user_by_username(username)
+            .await?
+            .ok_or(AppError::NotFound)?;
+
+        if user.role != ADMIN_ROLE {
+            self.repository.update_role(user.id, ADMIN_ROLE).await?;
+        }
+
+        Ok(())
+    }
+
+    // 管理者か、指定したエリアを担当するディスパッチャだけがエリアの情報を参照できる
+    #[instrument(skip(self, session), fields(user_id = session.user_id))]
+    pub async fn authorize_area(&self, session: &Session, area_id: i32) -> Result<(), AppError> {
+        let user = self
+            .repository
+            .find_user_by_id(session.user_id)
+            .await?
+            .ok_or(AppError::Unauthorized)?;
+
+        match user.role.as_str() {
+            ADMIN_ROLE => Ok(()),
+            "dispatcher" => match self.repository.find_dispatcher_by_user_id(user.id).await? {
+                Some(dispatcher) if dispatcher.area_id == area_id => Ok(()),
+                _ => Err(AppError::Forbidden),
+            },
+            _ => Err(AppError::Forbidden),
+        }
+    }
+
+    #[instrument(skip(self, session), fields(user_id = session.user_id))]
+    pub async fn issue_password_reset_token(
+        &self,
+        session: &Session,
+        target_user_id: i32,
+    ) -> Result<PasswordResetTokenResponseDto, AppError> {
+        match self.repository.find_user_by_id(session.user_id).await? {
+            Some(user) if user.role == ADMIN_ROLE => {}
+            _ => return Err(AppError::Forbidden),
+        }
+
+        if self
+            .repository
+            .find_user_by_id(target_user_id)
+            .await?
+            .is_none()
+        {
+            return Err(AppError::NotFound);
+        }
+
+        let reset_token = generate_password_reset_token();
+        let expires_at = Utc::now() + Duration::minutes(PASSWORD_RESET_TOKEN_LIFETIME_MINUTES);
+        self.repository
+            .create_password_reset_token(target_user_id, &reset_token, expires_at)
+            .await?;
+
+        Ok(PasswordResetTokenResponseDto {
+            user_id: target_user_id,
+            reset_token,
+            expires_at,
+        })
+    }
+
+    #[instrument(skip_all)]
+    pub async fn reset_password(
+        &self,
+        reset_token: &str,
+        new_password: &str,
+    ) -> Result<(), AppError> {
+        let token = match self
+            .repository
+            .find_password_reset_token(reset_token)
+            .await?
+        {
+            Some(token) if token.used_at.is_none() && token.expires_at > Utc::now() => token,
+            _ => return Err(AppError::Unauthorized),
+        };
+
+        // 同じトークンが並行して使われた場合でも一度しか成功しないようにする
+        if !self
+            .repository
+            .mark_password_reset_token_used(token.id)
+            .await?
+        {
+            return Err(AppError::Unauthorized);
+        }
+
+        let hashed_password = hash_password(new_password)?;
+        self.repository
+            .update_password(token.user_id, &hashed_password)
+            .await?;
+        self.repository
+            .invalidate_sessions_by_user_id(token.user_id, None)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::in_memory::InMemoryAuthRepository;
+
+    fn user(id: i32, username: &str, password: &str, role: &str) -> User {
+        User {
+            id,
+            username: username.to_string(),
+            password: hash_password(password).unwrap(),
+            profile_image: "default.png".to_string(),
+            role: role.to_string(),
+        }
+    }
+
+    fn service(users: Vec<User>) -> (AuthService<InMemoryAuthRepository>, InMemoryAuthRepository) {
+        service_with_session(users, SessionConfig::default())
+    }
+
+    fn service_with_session(
+        users: Vec<User>,
+        session: SessionConfig,
+    ) -> (AuthService<InMemoryAuthRepository>, InMemoryAuthRepository) {
+        let repository = InMemoryAuthRepository::new(users, vec![]);
+        let service = AuthService::new(
+            repository.clone(),
+            &ImageConfig {
+                profile_image_dir: "images/user_profile".to_string(),
+                cache_dir: "images/cache".to_string(),
+                cache_capacity: 1,
+            },
+            &session,
+        );
+        (service, repository)
+    }
+
+    async fn login(service: &AuthService<InMemoryAuthRepository>, username: &str) -> Session {
+        let response = service
+            .login_user(username, "password1", None)
+            .await
+            .unwrap();
+        service
+            .find_valid_session(&response.session_token)
+            .await
+            .unwrap()
+    }
+
+    fn password_of(repository: &InMemoryAuthRepository, user_id: i32) -> String {
+        let state = repository.state.lock().unwrap();
+        state
+            .users
+            .iter()
+            .find(|u| u.id == user_id)
+            .unwrap()
+            .password
+            .clone()
+    }
+
+    #[actix_rt::test]
+    async fn change_password_keeps_current_session_and_invalidates_others() {
+        let (service, repository) = service(vec![user(1, "client", "password1", "client")]);
+        let current = login(&service, "client").await;
+        let other = login(&service, "client").await;
+
+        service
+            .change_password(&current, "password1", "password2", None)
+            .await
+            .unwrap();
+
+        assert!(verify_password(&password_of(&repository, 1), "password2").unwrap());
+        assert!(service
+            .validate_session(&current.session_token)
+            .await
+            .unwrap());
+        assert!(!service
+            .validate_session(&other.session_token)
+            .await
+            .unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn change_password_rejects_wrong_current_password() {
+        let (service, repository) = service(vec![user(1, "client", "password1", "client")]);
+        let current = login(&service, "client").await;
+
+        let err = service
+            .change_password(&current, "wrong-password1", "password2", None)
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.code(), "unauthorized");
+        assert!(verify_password(&password_of(&repository, 1), "password1").unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn change_password_locks_out_repeated_wrong_passwords() {
+        let (service, repository) = service(vec![user(1, "client", "password1", "client")]);
+        let current = login(&service, "client").await;
+
+        for _ in 0..6 {
+            let err = service
+                .change_password(&current, "wrong-password1", "password2", Some("10.0.0.1"))
+                .await
+                .unwrap_err();
+            assert_eq!(err.code(), "unauthorized");
+        }
+
+        // ロック中は正しいパスワードでも変更できず、ログインも同じく制限される
+        let err = service
+            .change_password(&current, "password1", "password2", Some("10.0.0.1"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "too_many_requests");
+        assert!(matches!(
+            service.login_user("client", "password1", None).await,
+            Err(AppError::TooManyRequests { .. })
+        ));
+        assert!(verify_password(&password_of(&repository, 1), "password1").unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn issue_password_reset_token_requires_admin() {
+        let (service, _) = service(vec![
+            user(1, "client", "password1", "client"),
+            user(2, "other", "password1", "client"),
+        ]);
+        let session = login(&service, "client").await;
+
+        let err = service
+            .issue_password_reset_token(&session, 2)
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.code(), "forbidden");
+    }
+
+    #[actix_rt::test]
+    async fn issue_password_reset_token_rejects_unknown_user() {
+        let (service, _) = service(vec![user(1, "admin", "password1", ADMIN_ROLE)]);
+        let session = login(&service, "admin").await;
+
+        let err = service
+            .issue_password_reset_token(&session, 404)
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.code(), "not_found");
+    }
+
+    #[actix_rt::test]
+    async fn reset_password_updates_password_and_invalidates_sessions() {
+        let (service, repository) = service(vec![
+            user(1, "admin", "password1", ADMIN_ROLE),
+            user(2, "client", "password1", "client"),
+        ]);
+        let admin_session = login(&service, "admin").await;
+        let client_session = login(&service, "client").await;
+        let token = service
+            .issue_password_reset_token(&admin_session, 2)
+            .await
+            .unwrap();
+
+        service
+            .reset_password(&token.reset_token, "password2")
+            .await
+            .unwrap();
+
+        assert!(verify_password(&password_of(&repository, 2), "password2").unwrap());
+        assert!(!service
+            .validate_session(&client_session.session_token)
+            .await
+            .unwrap());
+
+        // 同じトークンは二度使えない
+        let err = service
+            .reset_password(&token.reset_token, "password3")
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "unauthorized");
+    }
+
+    #[actix_rt::test]
+    async fn reset_password_rejects_expired_token() {
+        let (service, repository) = service(vec![user(1, "client", "password1", "client")]);
+        repository
+            .create_password_reset_token(1, "expired", Utc::now() - Duration::seconds(1))
+            .await
+            .unwrap();
+
+        let err = service
+            .reset_password("expired", "password2")
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.code(), "unauthorized");
+        assert!(verify_password(&password_of(&repository, 1), "password1").unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn reset_password_rejects_unknown_token() {
+        let (service, _) = service(vec![]);
+
+        let err = service
+            .reset_password("unknown", "password2")
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.code(), "unauthorized");
+    }
+
+    #[actix_rt::test]
+    async fn grant_admin_updates_role() {
+        let (service, repository) = service(vec![user(1, "client", "password1", "client")]);
+
+        service.grant_admin("client").await.unwrap();
+
+        let admin = repository.find_user_by_id(1).await.unwrap().unwrap();
+        assert_eq!(admin.role, ADMIN_ROLE);
+    }
+
+    #[actix_rt::test]
+    async fn grant_admin_rejects_unknown_user() {
+        let (service, _) = service(vec![]);
+
+        let err = service.grant_admin("unknown").await.unwrap_err();
+
+        assert_eq!(err.code(), "not_found");
+    }
+
+    fn create_session_at(
+        repository: &InMemoryAuthRepository,
+        token: &str,
+        created_at: DateTime<Utc>,
+    ) {
+        let mut state = repository.state.lock().unwrap();
+        let id = state.sessions.len() as i32 + 1;
+        state.sessions.push(Session {
+            id,
+            user_id: 1,
+            session_token: token.to_string(),
+            is_valid: true,
+            created_at,
+        });
+    }
+
+    #[actix_rt::test]
+    async fn sessions_expire_after_lifetime() {
+        let (service, repository) = service_with_session(
+            vec![user(1, "client", "password1", "client")],
+            SessionConfig {
+                lifetime_secs: Some(3600),
+            },
+        );
+        create_session_at(&repository, "fresh", Utc::now() - Duration::minutes(59));
+        create_session_at(&repository, "expired", Utc::now() - Duration::minutes(61));
+
+        assert!(service.validate_session("fresh").await.unwrap());
+        assert!(service.find_valid_session("fresh").await.is_ok());
+        assert!(!service.validate_session("expired").await.unwrap());
+        assert_eq!(
+            service
+                .find_valid_session("expired")
+                .await
+                .unwrap_err()
+                .code(),
+            "unauthorized"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn sessions_without_lifetime_do_not_expire() {
+        let (service, repository) = service(vec![user(1, "client", "password1", "client")]);
+        create_session_at(&repository, "old", Utc::now() - Duration::days(365));
+
+        assert!(service.validate_session("old").await.unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn cleanup_sessions_deletes_expired_and_invalidated_sessions() {
+        let (service, repository) = service_with_session(
+            vec![user(1, "client", "password1", "client")],
+            SessionConfig {
+                lifetime_secs: Some(3600),
+            },
+        );
+        create_session_at(&repository, "fresh", Utc::now());
+        create_session_at(&repository, "expired", Utc::now() - Duration::hours(2));
+        create_session_at(&repository, "logged_out", Utc::now());
+        service.logout_user("logged_out").await.unwrap();
+
+        assert_eq!(service.cleanup_sessions().await.unwrap(), 2);
+        assert!(service.validate_session("fresh").await.unwrap());
+        assert!(service.validate_session("expired").await.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn authorize_area_allows_admins_and_dispatchers_of_the_area() {
+        let (service, repository) = service(vec![
+            user(1, "admin", "password1", ADMIN_ROLE),
+            user(2, "dispatcher", "password1", "dispatcher"),
+        ]);
+        repository.create_dispatcher(2, 1).await.unwrap();
+        let admin = login(&service, "admin").await;
+        let dispatcher = login(&service, "dispatcher").await;
+
+        service.authorize_area(&admin, 2).await.unwrap();
+        service.authorize_area(&dispatcher, 1).await.unwrap();
+        let err = service.authorize_area(&dispatcher, 2).await.unwrap_err();
+        assert_eq!(err.code(), "forbidden");
+    }
+
+    #[actix_rt::test]
+    async fn authorize_area_rejects_other_roles() {
+        let (service, _) = service(vec![
+            user(1, "client", "password1", "client"),
+            user(2, "driver", "password1", "driver"),
+        ]);
+
+        for username in ["client", "driver"] {
+            let session = login(&service, username).await;
+            let err = service.authorize_area(&session, 1).await.unwrap_err();
+            assert_eq!(err.code(), "forbidden");
+        }
     }
 }
@@ -0,0 +1,541 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use tracing::instrument;
+
+use super::dto::driver::{
+    DriverJobAction, DriverJobDto, DriverJobSummaryDto, DriverRouteDto, DriverShiftDto,
+    NodeLocationDto,
+};
+use super::map_service::{load_area_graph, MapRepository};
+use super::order_events::{OrderEvent, OrderEventBus, OrderEventKind};
+use super::order_service::OrderRepository;
+use super::tow_truck_events::{TowTruckEvent, TowTruckEventBus, TowTruckEventKind};
+use super::tow_truck_service::{publish_tow_truck_event, TowTruckRepository};
+use crate::errors::AppError;
+use crate::infrastructure::graph_cache::GraphCache;
+use crate::models::{order::Order, tow_truck::TowTruck};
+
+#[derive(Debug)]
+pub struct DriverService<
+    T: TowTruckRepository + std::fmt::Debug,
+    U: OrderRepository + std::fmt::Debug,
+    V: MapRepository + std::fmt::Debug,
+> {
+    tow_truck_repository: T,
+    order_repository: U,
+    map_repository: V,
+    graph_cache: Arc<GraphCache>,
+    order_events: Arc<OrderEventBus>,
+    tow_truck_events: Arc<TowTruckEventBus>,
+}
+
+impl<
+        T: TowTruckRepository + std::fmt::Debug,
+        U: OrderRepository + std::fmt::Debug,
+        V: MapRepository + std::fmt::Debug,
+    > DriverService<T, U, V>
+{
+    pub fn new(
+        tow_truck_repository: T,
+        order_repository: U,
+        map_repository: V,
+        graph_cache: Arc<GraphCache>,
+        order_events: Arc<OrderEventBus>,
+        tow_truck_events: Arc<TowTruckEventBus>,
+    ) -> Self {
+        DriverService {
+            tow_truck_repository,
+            order_repository,
+            map_repository,
+            graph_cache,
+            order_events,
+            tow_truck_events,
+        }
+    }
+
+    // ドライバーに割り当てられているレッカー車を返す (レッカー車を持たないユーザーは操作できない)
+    async fn find_tow_truck(&self, user_id: i32) -> Result<TowTruck, AppError> {
+        self.tow_truck_repository
+            .find_tow_truck_by_driver_id(user_id)
+            .await?
+            .ok_or(AppError::Forbidden)
+    }
+
+    #[instrument(skip(self))]
+    pub async fn get_current_job(&self, user_id: i32) -> Result<Option<DriverJobDto>, AppError> {
+        let tow_truck = self.find_tow_truck(user_id).await?;
+        let order = match self
+            .order_repository
+            .find_active_order_by_tow_truck_id(tow_truck.id)
+            .await?
+        {
+            Some(order) => order,
+            None => return Ok(None),
+        };
+
+        let graph =
+            load_area_graph(&self.map_repository, &self.graph_cache, tow_truck.area_id).await?;
+        let client_location = match graph.nodes.get(&order.node_id) {
+            Some(node) => NodeLocationDto::from_entity(node),
+            None => return Err(AppError::NotFound),
+        };
+        let route =
+            graph
+                .shortest_route(tow_truck.node_id, order.node_id)
+                .map(|(distance, node_ids)| DriverRouteDto {
+                    distance,
+                    nodes: node_ids
+                        .iter()
+                        .filter_map(|node_id| graph.nodes.get(node_id))
+                        .map(NodeLocationDto::from_entity)
+                        .collect(),
+                });
+
+        Ok(Some(DriverJobDto {
+            order_id: order.id,
+            tow_truck_id: tow_truck.id,
+            status: order.status,
+            client_id: order.client_id,
+            car_value: order.car_value,
+            order_time: order.order_time,
+            client_location,
+            route,
+        }))
+    }
+
+    #[instrument(skip(self))]
+    pub async fn get_jobs(
+        &self,
+        user_id: i32,
+        page: i32,
+        page_size: i32,
+    ) -> Result<Vec<DriverJobSummaryDto>, AppError> {
+        if page < 0 || page_size < 1 {
+            return Err(AppError::BadRequest);
+        }
+
+        let tow_truck = self.find_tow_truck(user_id).await?;
+        let orders = self
+            .order_repository
+            .get_orders_by_tow_truck_id(tow_truck.id, page, page_size)
+            .await?;
+
+        Ok(orders
+            .into_iter()
+            .map(DriverJobSummaryDto::from_entity)
+            .collect())
+    }
+
+    // 到着・積み込み・完了の報告を受けてオーダーのステータスを進める
+    #[instrument(skip(self))]
+    pub async fn update_job(
+        &self,
+        user_id: i32,
+        order_id: i32,
+        action: DriverJobAction,
+    ) -> Result<DriverJobSummaryDto, AppError> {
+        let tow_truck = self.find_tow_truck(user_id).await?;
+        let order = self.find_assigned_order(&tow_truck, order_id).await?;
+
+        if order.status != action.required_status() {
+            return Err(AppError::Conflict);
+        }
+
+        // 読み込んだ後に別の報告で進んでいた場合は、更新せずに競合として扱う
+        match action {
+            DriverJobAction::Arrive | DriverJobAction::Pickup => {
+                if !self
+                    .order_repository
+                    .advance_order_status(order.id, action.required_status(), action.next_status())
+                    .await?
+                {
+                    return Err(AppError::Conflict);
+                }
+            }
+            DriverJobAction::Complete => {
+                if !self
+                    .order_repository
+                    .update_order_completed(order.id, Utc::now())
+                    .await?
+                {
+                    return Err(AppError::Conflict);
+                }
+                // 作業が終わったレッカー車は次の配車を受けられるようにする
+                self.tow_truck_repository
+                    .update_status(tow_truck.id, "available")
+                    .await?;
+                self.tow_truck_events.publish(TowTruckEvent::new(
+                    TowTruckEventKind::StatusChanged,
+                    TowTruck {
+                        status: "available".to_string(),
+                        ..tow_truck.clone()
+                    },
+                ));
+            }
+        }
+
+        // 割り当てられたオーダーはレッカー車と同じエリアにあるため、読み直した結果からイベントを組み立てる
+        let order = self.order_repository.find_order_by_id(order.id).await?;
+        self.order_events.publish(OrderEvent::new(
+            OrderEventKind::StatusChanged,
+            &order,
+            tow_truck.area_id,
+        ));
+
+        Ok(DriverJobSummaryDto::from_entity(order))
+    }
+
+    // 勤務を開始し、勤務外だったレッカー車を配車可能にする
+    #[instrument(skip(self))]
+    pub async fn start_shift(&self, user_id: i32) -> Result<DriverShiftDto, AppError> {
+        let tow_truck = self.find_tow_truck(user_id).await?;
+        let shift_id = self
+            .tow_truck_repository
+            .start_shift(tow_truck.id, user_id, tow_truck.area_id, Utc::now())
+            .await?;
+        publish_tow_truck_event(
+            &self.tow_truck_repository,
+            &self.tow_truck_events,
+            TowTruckEventKind::StatusChanged,
+            tow_truck.id,
+        )
+        .await;
+
+        self.find_shift(shift_id).await
+    }
+
+    // 勤務を終了し、レッカー車を配車の対象から外す (配車中は終了できない)
+    #[instrument(skip(self))]
+    pub async fn end_shift(&self, user_id: i32) -> Result<DriverShiftDto, AppError> {
+        let shift = self
+            .tow_truck_repository
+            .find_open_shift_by_driver_id(user_id)
+            .await?
+            .ok_or(AppError::Conflict)?;
+
+        if !self
+            .tow_truck_repository
+            .end_shift(shift.id, shift.tow_truck_id, Utc::now())
+            .await?
+        {
+            return Err(AppError::Conflict);
+        }
+        publish_tow_truck_event(
+            &self.tow_truck_repository,
+            &self.tow_truck_events,
+            TowTruckEventKind::StatusChanged,
+            shift.tow_truck_id,
+        )
+        .await;
+
+        self.find_shift(shift.id).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn get_shifts(
+        &self,
+        user_id: i32,
+        page: i32,
+        page_size: i32,
+    ) -> Result<Vec<DriverShiftDto>, AppError> {
+        if page < 0 || page_size < 1 {
+            return Err(AppError::BadRequest);
+        }
+
+        let now = Utc::now();
+        let shifts = self
+            .tow_truck_repository
+            .get_shifts_by_driver_id(user_id, page, page_size)
+            .await?;
+
+        Ok(shifts
+            .into_iter()
+            .map(|shift| DriverShiftDto::from_entity(shift, now))
+            .collect())
+    }
+
+    async fn find_shift(&self, shift_id: i32) -> Result<DriverShiftDto, AppError> {
+        let shift = self
+            .tow_truck_repository
+            .find_shift_by_id(shift_id)
+            .await?
+            .ok_or(AppError::NotFound)?;
+
+        Ok(DriverShiftDto::from_entity(shift, Utc::now()))
+    }
+
+    // 他のレッカー車に割り当てられたオーダーは存在しないものとして扱う
+    async fn find_assigned_order(
+        &self,
+        tow_truck: &TowTruck,
+        order_id: i32,
+    ) -> Result<Order, AppError> {
+        let order = self.order_repository.find_order_by_id(order_id).await?;
+        if order.tow_truck_id != Some(tow_truck.id) {
+            return Err(AppError::NotFound);
+        }
+
+        Ok(order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::models::graph::Edge;
+    use crate::repositories::in_memory::{
+        InMemoryMapRepository, InMemoryOrderRepository, InMemoryTowTruckRepository,
+    };
+
+    type TestDriverService =
+        DriverService<InMemoryTowTruckRepository, InMemoryOrderRepository, InMemoryMapRepository>;
+
+    const DRIVER_ID: i32 = 30;
+
+    fn tow_truck(id: i32, driver_id: i32, status: &str) -> TowTruck {
+        TowTruck {
+            id,
+            driver_id,
+            driver_username: None,
+            status: status.to_string(),
+            area_id: 1,
+            node_id: 1,
+        }
+    }
+
+    fn order(id: i32, tow_truck_id: i32, status: &str) -> Order {
+        Order {
+            id,
+            client_id: 10,
+            dispatcher_id: Some(1),
+            tow_truck_id: Some(tow_truck_id),
+            status: status.to_string(),
+            node_id: 3,
+            car_value: 1000.0,
+            order_time: Utc.with_ymd_and_hms(2024, 7, 25, 1, 0, 0).unwrap(),
+            completed_time: None,
+        }
+    }
+
+    fn service(tow_trucks: Vec<TowTruck>, orders: Vec<Order>) -> TestDriverService {
+        DriverService::new(
+            InMemoryTowTruckRepository::new(tow_trucks),
+            InMemoryOrderRepository::new(orders),
+            InMemoryMapRepository::new(
+                HashMap::from([(1, 1), (2, 1), (3, 1)]),
+                vec![
+                    Edge {
+                        node_a_id: 1,
+                        node_b_id: 2,
+                        weight: 4,
+                    },
+                    Edge {
+                        node_a_id: 2,
+                        node_b_id: 3,
+                        weight: 6,
+                    },
+                ],
+            ),
+            Arc::new(GraphCache::new()),
+            Arc::new(OrderEventBus::new()),
+            Arc::new(TowTruckEventBus::new()),
+        )
+    }
+
+    #[actix_rt::test]
+    async fn get_current_job_returns_route_to_client() {
+        let service = service(
+            vec![tow_truck(1, DRIVER_ID, "busy")],
+            vec![order(1, 1, "dispatched")],
+        );
+
+        let job = service.get_current_job(DRIVER_ID).await.unwrap().unwrap();
+
+        assert_eq!(job.order_id, 1);
+        let route = job.route.unwrap();
+        assert_eq!(route.distance, 10);
+        assert_eq!(route.nodes.len(), 3);
+    }
+
+    #[actix_rt::test]
+    async fn update_job_advances_through_arrive_pickup_and_complete() {
+        let service = service(
+            vec![tow_truck(1, DRIVER_ID, "busy")],
+            vec![order(1, 1, "dispatched")],
+        );
+        let mut order_events = service.order_events.subscribe();
+        let mut tow_truck_events = service.tow_truck_events.subscribe();
+
+        let job = service
+            .update_job(DRIVER_ID, 1, DriverJobAction::Arrive)
+            .await
+            .unwrap();
+        assert_eq!(job.status, "arrived");
+        let job = service
+            .update_job(DRIVER_ID, 1, DriverJobAction::Pickup)
+            .await
+            .unwrap();
+        assert_eq!(job.status, "picked_up");
+        let job = service
+            .update_job(DRIVER_ID, 1, DriverJobAction::Complete)
+            .await
+            .unwrap();
+        assert_eq!(job.status, "completed");
+        assert!(job.completed_time.is_some());
+
+        // 完了したレッカー車は再び配車できる
+        let tow_truck = service.tow_truck_repository.tow_truck(1).unwrap();
+        assert_eq!(tow_truck.status, "available");
+        assert!(service.get_current_job(DRIVER_ID).await.unwrap().is_none());
+
+        let statuses: Vec<String> = (0..3)
+            .map(|_| order_events.try_recv().unwrap().status)
+            .collect();
+        assert_eq!(statuses, vec!["arrived", "picked_up", "completed"]);
+        let event = tow_truck_events.try_recv().unwrap();
+        assert_eq!(event.tow_truck.status, "available");
+    }
+
+    #[actix_rt::test]
+    async fn update_job_rejects_out_of_order_actions() {
+        let service = service(
+            vec![tow_truck(1, DRIVER_ID, "busy")],
+            vec![order(1, 1, "dispatched")],
+        );
+
+        let err = service
+            .update_job(DRIVER_ID, 1, DriverJobAction::Complete)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "conflict");
+
+        service
+            .update_job(DRIVER_ID, 1, DriverJobAction::Arrive)
+            .await
+            .unwrap();
+        // 同じ報告を繰り返しても進まない
+        let err = service
+            .update_job(DRIVER_ID, 1, DriverJobAction::Arrive)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "conflict");
+        let order = service.order_repository.order(1).unwrap();
+        assert_eq!(order.status, "arrived");
+    }
+
+    #[actix_rt::test]
+    async fn update_job_hides_orders_of_other_tow_trucks() {
+        let service = service(
+            vec![
+                tow_truck(1, DRIVER_ID, "busy"),
+                tow_truck(2, DRIVER_ID + 1, "busy"),
+            ],
+            vec![order(1, 2, "dispatched")],
+        );
+
+        let err = service
+            .update_job(DRIVER_ID, 1, DriverJobAction::Arrive)
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.code(), "not_found");
+    }
+
+    #[actix_rt::test]
+    async fn update_job_requires_tow_truck() {
+        let service = service(vec![], vec![order(1, 1, "dispatched")]);
+
+        let err = service
+            .update_job(DRIVER_ID, 1, DriverJobAction::Arrive)
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.code(), "forbidden");
+    }
+
+    #[actix_rt::test]
+    async fn start_shift_puts_off_duty_tow_truck_on_duty() {
+        let service = service(vec![tow_truck(1, DRIVER_ID, "off_duty")], vec![]);
+        let mut tow_truck_events = service.tow_truck_events.subscribe();
+
+        let shift = service.start_shift(DRIVER_ID).await.unwrap();
+
+        assert_eq!(shift.tow_truck_id, 1);
+        assert_eq!(shift.driver_user_id, DRIVER_ID);
+        assert_eq!(shift.area_id, 1);
+        assert_eq!(shift.ended_at, None);
+        let tow_truck = service.tow_truck_repository.tow_truck(1).unwrap();
+        assert_eq!(tow_truck.status, "available");
+        let event = tow_truck_events.try_recv().unwrap();
+        assert_eq!(event.tow_truck.status, "available");
+    }
+
+    #[actix_rt::test]
+    async fn start_shift_twice_conflicts() {
+        let service = service(vec![tow_truck(1, DRIVER_ID, "off_duty")], vec![]);
+        service.start_shift(DRIVER_ID).await.unwrap();
+
+        let err = service.start_shift(DRIVER_ID).await.unwrap_err();
+
+        assert_eq!(err.code(), "conflict");
+    }
+
+    #[actix_rt::test]
+    async fn end_shift_takes_tow_truck_off_duty() {
+        let service = service(vec![tow_truck(1, DRIVER_ID, "off_duty")], vec![]);
+        let started = service.start_shift(DRIVER_ID).await.unwrap();
+
+        let ended = service.end_shift(DRIVER_ID).await.unwrap();
+
+        assert_eq!(ended.shift_id, started.shift_id);
+        assert!(ended.ended_at.is_some());
+        let tow_truck = service.tow_truck_repository.tow_truck(1).unwrap();
+        assert_eq!(tow_truck.status, "off_duty");
+        // 終了後は再び勤務を開始できる
+        service.start_shift(DRIVER_ID).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn end_shift_without_open_shift_conflicts() {
+        let service = service(vec![tow_truck(1, DRIVER_ID, "off_duty")], vec![]);
+
+        let err = service.end_shift(DRIVER_ID).await.unwrap_err();
+
+        assert_eq!(err.code(), "conflict");
+    }
+
+    #[actix_rt::test]
+    async fn end_shift_while_dispatched_conflicts() {
+        let service = service(vec![tow_truck(1, DRIVER_ID, "off_duty")], vec![]);
+        service.start_shift(DRIVER_ID).await.unwrap();
+        service
+            .tow_truck_repository
+            .update_status(1, "busy")
+            .await
+            .unwrap();
+
+        let err = service.end_shift(DRIVER_ID).await.unwrap_err();
+
+        assert_eq!(err.code(), "conflict");
+        let shift = service
+            .tow_truck_repository
+            .find_open_shift_by_driver_id(DRIVER_ID)
+            .await
+            .unwrap();
+        assert!(shift.is_some());
+    }
+
+    #[actix_rt::test]
+    async fn shift_requires_tow_truck() {
+        let service = service(vec![], vec![]);
+
+        let err = service.start_shift(DRIVER_ID).await.unwrap_err();
+
+        assert_eq!(err.code(), "forbidden");
+    }
+}
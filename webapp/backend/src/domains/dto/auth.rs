@@ -1,26 +1,53 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use super::validators::{validate_password, validate_role, validate_username};
 
 // Input Data Structure
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Validate)]
 pub struct RegisterRequestDto {
+    #[validate(custom = "validate_username")]
     pub username: String,
+    #[validate(custom = "validate_password")]
     pub password: String,
+    #[validate(custom = "validate_role")]
     pub role: String,
+    #[validate(range(min = 1))]
     pub area_id: Option<i32>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Validate)]
 pub struct LoginRequestDto {
+    #[validate(length(min = 1, max = 255))]
     pub username: String,
+    #[validate(length(min = 1, max = 128))]
     pub password: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate)]
 pub struct LogoutRequestDto {
+    #[validate(length(min = 1, max = 255))]
     pub session_token: String,
 }
 
+#[derive(Deserialize, Debug, Validate)]
+pub struct ChangePasswordRequestDto {
+    #[validate(length(min = 1, max = 128))]
+    pub current_password: String,
+    #[validate(custom = "validate_password")]
+    pub new_password: String,
+}
+
+#[derive(Deserialize, Debug, Validate)]
+pub struct ResetPasswordRequestDto {
+    #[validate(length(min = 1, max = 255))]
+    pub reset_token: String,
+    #[validate(custom = "validate_password")]
+    pub new_password: String,
+}
+
 // Output Data Structure
 
 #[derive(Serialize)]
@@ -32,3 +59,15 @@ pub struct LoginResponseDto {
     pub dispatcher_id: Option<i32>,
     pub area_id: Option<i32>,
 }
+
+#[derive(Serialize, Debug)]
+pub struct PasswordResetTokenResponseDto {
+    pub user_id: i32,
+    pub reset_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct ProfileImageResponseDto {
+    pub profile_image: String,
+}
@@ -0,0 +1,124 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{graph::Node, order::Order, tow_truck::DriverShift};
+
+// Input Data Structure
+
+// ドライバーが現場で報告する作業の区切り
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DriverJobAction {
+    Arrive,
+    Pickup,
+    Complete,
+}
+
+impl DriverJobAction {
+    // この操作を受け付けるオーダーのステータス
+    pub fn required_status(&self) -> &'static str {
+        match self {
+            DriverJobAction::Arrive => "dispatched",
+            DriverJobAction::Pickup => "arrived",
+            DriverJobAction::Complete => "picked_up",
+        }
+    }
+
+    // 操作後のオーダーのステータス
+    pub fn next_status(&self) -> &'static str {
+        match self {
+            DriverJobAction::Arrive => "arrived",
+            DriverJobAction::Pickup => "picked_up",
+            DriverJobAction::Complete => "completed",
+        }
+    }
+}
+
+// Output Data Structure
+
+#[derive(Serialize, Debug)]
+pub struct NodeLocationDto {
+    pub node_id: i32,
+    pub x: i32,
+    pub y: i32,
+}
+
+impl NodeLocationDto {
+    pub fn from_entity(entity: &Node) -> Self {
+        NodeLocationDto {
+            node_id: entity.id,
+            x: entity.x,
+            y: entity.y,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct DriverRouteDto {
+    pub distance: i32,
+    pub nodes: Vec<NodeLocationDto>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DriverJobDto {
+    pub order_id: i32,
+    pub tow_truck_id: i32,
+    pub status: String,
+    pub client_id: i32,
+    pub car_value: f64,
+    pub order_time: DateTime<Utc>,
+    // 依頼者の車がある地点
+    pub client_location: NodeLocationDto,
+    // レッカー車の現在地から依頼者の地点までの経路 (到達できない場合は None)
+    pub route: Option<DriverRouteDto>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DriverJobSummaryDto {
+    pub order_id: i32,
+    pub status: String,
+    pub node_id: i32,
+    pub car_value: f64,
+    pub order_time: DateTime<Utc>,
+    pub completed_time: Option<DateTime<Utc>>,
+}
+
+impl DriverJobSummaryDto {
+    pub fn from_entity(entity: Order) -> Self {
+        DriverJobSummaryDto {
+            order_id: entity.id,
+            status: entity.status,
+            node_id: entity.node_id,
+            car_value: entity.car_value,
+            order_time: entity.order_time,
+            completed_time: entity.completed_time,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct DriverShiftDto {
+    pub shift_id: i32,
+    pub tow_truck_id: i32,
+    pub driver_user_id: i32,
+    pub area_id: i32,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    // 勤務中のシフトは now までの経過時間
+    pub duration_secs: i64,
+}
+
+impl DriverShiftDto {
+    pub fn from_entity(entity: DriverShift, now: DateTime<Utc>) -> Self {
+        let ended_at = entity.ended_at.unwrap_or(now);
+        DriverShiftDto {
+            shift_id: entity.id,
+            tow_truck_id: entity.tow_truck_id,
+            driver_user_id: entity.driver_id,
+            area_id: entity.area_id,
+            started_at: entity.started_at,
+            ended_at: entity.ended_at,
+            duration_secs: (ended_at - entity.started_at).num_seconds(),
+        }
+    }
+}
@@ -0,0 +1,12 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+// Output Data Structure
+
+#[derive(Serialize, Debug, Clone)]
+pub struct EtaDto {
+    // 出発地から目的地までの最短経路のコスト (辺の重みの合計)
+    pub distance: i32,
+    pub travel_time_secs: i64,
+    pub estimated_arrival_at: DateTime<Utc>,
+}
@@ -1,10 +1,57 @@
 // Input Data Structure
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
 
-#[derive(Deserialize, Debug)]
+use crate::models::graph::EdgeUpdate;
+
+// 一括更新で一度に受け付ける辺の数の上限
+const MAX_BULK_EDGE_UPDATES: u64 = 1000;
+
+#[derive(Deserialize, Debug, Validate)]
 pub struct UpdateEdgeRequestDto {
+    #[validate(range(min = 1))]
     pub node_a_id: i32,
+    #[validate(range(min = 1))]
     pub node_b_id: i32,
+    #[validate(range(min = 0))]
     pub weight: i32,
 }
+
+// weight と is_closed のうち指定された項目だけを変更する
+#[derive(Deserialize, Serialize, Debug, Validate)]
+pub struct BulkUpdateEdgeDto {
+    #[validate(range(min = 1))]
+    pub node_a_id: i32,
+    #[validate(range(min = 1))]
+    pub node_b_id: i32,
+    #[validate(range(min = 0))]
+    pub weight: Option<i32>,
+    pub is_closed: Option<bool>,
+}
+
+impl BulkUpdateEdgeDto {
+    pub fn to_entity(&self) -> EdgeUpdate {
+        EdgeUpdate {
+            node_a_id: self.node_a_id,
+            node_b_id: self.node_b_id,
+            weight: self.weight,
+            is_closed: self.is_closed,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Validate)]
+pub struct BulkUpdateEdgesRequestDto {
+    #[validate(length(min = 1, max = "MAX_BULK_EDGE_UPDATES"))]
+    #[validate]
+    pub edges: Vec<BulkUpdateEdgeDto>,
+}
+
+#[derive(Deserialize, Debug, Validate)]
+pub struct EdgeClosureRequestDto {
+    #[validate(range(min = 1))]
+    pub node_a_id: i32,
+    #[validate(range(min = 1))]
+    pub node_b_id: i32,
+}
@@ -1,4 +1,7 @@
 pub mod auth;
+pub mod driver;
+pub mod eta;
 pub mod map;
 pub mod order;
 pub mod tow_truck;
+pub mod validators;
@@ -1,26 +1,36 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use super::eta::EtaDto;
+use super::validators::{validate_order_status, validate_positive_amount};
 
 // Input Data Structure
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Validate)]
 pub struct ClientOrderRequestDto {
-    pub client_id: i32,
+    #[validate(range(min = 1))]
     pub node_id: i32,
+    #[validate(custom = "validate_positive_amount")]
     pub car_value: f64,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Validate)]
 pub struct DispatcherOrderRequestDto {
+    #[validate(range(min = 1))]
     pub order_id: i32,
+    #[validate(range(min = 1))]
     pub dispatcher_id: i32,
+    #[validate(range(min = 1))]
     pub tow_truck_id: i32,
     pub order_time: DateTime<Utc>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Validate)]
 pub struct UpdateOrderStatusRequestDto {
+    #[validate(range(min = 1))]
     pub order_id: i32,
+    #[validate(custom = "validate_order_status")]
     pub status: String,
 }
 
@@ -43,14 +53,39 @@ pub struct OrderDto {
     pub car_value: f64,
     pub order_time: DateTime<Utc>,
     pub completed_time: Option<DateTime<Utc>>,
+    // 詳細の取得時のみ、配車済みのオーダーに設定する
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eta: Option<EtaDto>,
 }
 
 #[derive(Serialize, Debug)]
-pub struct CompletedOrderDto {
-    pub id: i32,
+pub struct CreatedOrderDto {
     pub order_id: i32,
-    pub tow_truck_id: i32,
-    pub order_time: Option<DateTime<Utc>>,
-    pub completed_time: DateTime<Utc>,
-    pub car_value: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct OrderTrackingDto {
+    pub order_id: i32,
+    pub status: String,
+    pub node_id: i32,
+    pub area_id: i32,
+    pub order_time: DateTime<Utc>,
+    pub completed_time: Option<DateTime<Utc>>,
+    pub driver: Option<TrackingDriverDto>,
+    pub tow_truck: Option<TrackingTowTruckDto>,
+    // レッカー車が依頼者の地点へ向かっている間だけ設定する
+    pub eta: Option<EtaDto>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TrackingDriverDto {
+    pub user_id: i32,
+    pub username: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TrackingTowTruckDto {
+    pub id: i32,
+    pub status: String,
+    pub node_id: i32,
 }
@@ -1,10 +1,16 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use super::eta::EtaDto;
 
 // Input Data Structure
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Validate)]
 pub struct UpdateLocationRequestDto {
+    #[validate(range(min = 1))]
     pub tow_truck_id: i32,
+    #[validate(range(min = 1))]
     pub node_id: i32,
 }
 
@@ -20,6 +26,14 @@ pub struct TowTruckDto {
     pub area_id: i32,
 }
 
+// 最寄りのレッカー車と、依頼者の地点までの到着予定
+#[derive(Serialize)]
+pub struct NearestTowTruckDto {
+    #[serde(flatten)]
+    pub tow_truck: TowTruckDto,
+    pub eta: Option<EtaDto>,
+}
+
 impl TowTruckDto {
     pub fn from_entity(entity: crate::models::tow_truck::TowTruck) -> Self {
         TowTruckDto {
@@ -32,3 +46,33 @@ impl TowTruckDto {
         }
     }
 }
+
+#[derive(Serialize, Debug)]
+pub struct OnShiftDriverDto {
+    pub shift_id: i32,
+    pub tow_truck_id: i32,
+    pub driver_user_id: i32,
+    pub driver_username: String,
+    pub status: String,
+    pub area_id: i32,
+    pub started_at: DateTime<Utc>,
+    pub on_shift_secs: i64,
+}
+
+impl OnShiftDriverDto {
+    pub fn from_entity(
+        entity: crate::models::tow_truck::OnShiftDriver,
+        now: DateTime<Utc>,
+    ) -> Self {
+        OnShiftDriverDto {
+            shift_id: entity.shift_id,
+            tow_truck_id: entity.tow_truck_id,
+            driver_user_id: entity.driver_id,
+            driver_username: entity.driver_username,
+            status: entity.status,
+            area_id: entity.area_id,
+            started_at: entity.started_at,
+            on_shift_secs: (now - entity.started_at).num_seconds(),
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use validator::ValidationError;
+
+pub const ALLOWED_REGISTER_ROLES: [&str; 3] = ["client", "dispatcher", "driver"];
+pub const ALLOWED_ORDER_STATUSES: [&str; 6] = [
+    "pending",
+    "dispatched",
+    "arrived",
+    "picked_up",
+    "completed",
+    "cancelled",
+];
+
+pub fn validate_username(username: &str) -> Result<(), ValidationError> {
+    let is_valid_length = (3..=32).contains(&username.chars().count());
+    let is_valid_chars = username
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.');
+
+    if is_valid_length && is_valid_chars {
+        Ok(())
+    } else {
+        Err(with_message(
+            "username",
+            "3〜32文字の英数字、'_'、'-'、'.' のみ使用できます",
+        ))
+    }
+}
+
+pub fn validate_password(password: &str) -> Result<(), ValidationError> {
+    let length = password.chars().count();
+    let has_letter = password.chars().any(|c| c.is_alphabetic());
+    let has_digit = password.chars().any(|c| c.is_ascii_digit());
+
+    if (8..=128).contains(&length) && has_letter && has_digit {
+        Ok(())
+    } else {
+        Err(with_message(
+            "password_policy",
+            "8〜128文字で、英字と数字をそれぞれ1文字以上含めてください",
+        ))
+    }
+}
+
+pub fn validate_role(role: &str) -> Result<(), ValidationError> {
+    if ALLOWED_REGISTER_ROLES.contains(&role) {
+        Ok(())
+    } else {
+        Err(with_message(
+            "role",
+            "client, dispatcher, driver のいずれかを指定してください",
+        ))
+    }
+}
+
+pub fn validate_order_status(status: &str) -> Result<(), ValidationError> {
+    if ALLOWED_ORDER_STATUSES.contains(&status) {
+        Ok(())
+    } else {
+        Err(with_message("order_status", "不正なステータスです"))
+    }
+}
+
+pub fn validate_positive_amount(value: f64) -> Result<(), ValidationError> {
+    if value.is_finite() && value > 0.0 {
+        Ok(())
+    } else {
+        Err(with_message("positive", "0より大きい値を指定してください"))
+    }
+}
+
+fn with_message(code: &'static str, message: &'static str) -> ValidationError {
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.into());
+    error
+}
+
+#[cfg(test)]
+mod tests {
+    use validator::Validate;
+
+    use super::*;
+    use crate::domains::dto::auth::RegisterRequestDto;
+
+    #[test]
+    fn username_accepts_allowed_characters_and_lengths() {
+        for username in ["abc", "user_name-1.x", &"a".repeat(32)] {
+            assert!(validate_username(username).is_ok(), "{}", username);
+        }
+    }
+
+    #[test]
+    fn username_rejects_bad_lengths_and_characters() {
+        for username in [
+            "ab",
+            &"a".repeat(33),
+            "user name",
+            "ユーザー名",
+            "user@example",
+        ] {
+            let err = validate_username(username).unwrap_err();
+            assert_eq!(err.code, "username", "{}", username);
+        }
+    }
+
+    #[test]
+    fn password_requires_letter_digit_and_length() {
+        assert!(validate_password("passw0rd").is_ok());
+        assert!(validate_password(&format!("a1{}", "x".repeat(126))).is_ok());
+
+        for password in [
+            "pass1",
+            "password",
+            "12345678",
+            &format!("a1{}", "x".repeat(127)),
+        ] {
+            let err = validate_password(password).unwrap_err();
+            assert_eq!(err.code, "password_policy", "{}", password);
+        }
+    }
+
+    #[test]
+    fn role_allows_only_registrable_roles() {
+        for role in ALLOWED_REGISTER_ROLES {
+            assert!(validate_role(role).is_ok());
+        }
+        for role in ["admin", "Client", ""] {
+            assert_eq!(validate_role(role).unwrap_err().code, "role");
+        }
+    }
+
+    #[test]
+    fn order_status_allows_known_statuses() {
+        for status in ALLOWED_ORDER_STATUSES {
+            assert!(validate_order_status(status).is_ok());
+        }
+        assert_eq!(
+            validate_order_status("unknown").unwrap_err().code,
+            "order_status"
+        );
+    }
+
+    #[test]
+    fn positive_amount_rejects_zero_negative_and_non_finite() {
+        assert!(validate_positive_amount(0.01).is_ok());
+        for value in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(
+                validate_positive_amount(value).unwrap_err().code,
+                "positive"
+            );
+        }
+    }
+
+    #[test]
+    fn register_request_reports_each_invalid_field() {
+        let request = RegisterRequestDto {
+            username: "a".to_string(),
+            password: "password".to_string(),
+            role: "admin".to_string(),
+            area_id: Some(0),
+        };
+
+        let errors = request.validate().unwrap_err();
+        let mut fields: Vec<&str> = errors.field_errors().into_keys().collect();
+        fields.sort();
+
+        assert_eq!(fields, vec!["area_id", "password", "role", "username"]);
+    }
+}
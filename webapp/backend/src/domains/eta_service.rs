@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+
+use super::dto::eta::EtaDto;
+use super::map_service::{load_area_graph, MapRepository};
+use super::tow_truck_service::calculate_distance;
+use crate::config::EtaConfig;
+use crate::errors::AppError;
+use crate::infrastructure::graph_cache::GraphCache;
+
+// 最短経路のコストを到着予定時刻に換算する
+#[derive(Debug)]
+pub struct EtaService {
+    graph_cache: Arc<GraphCache>,
+    weight_unit_secs: f64,
+    area_speed_factors: HashMap<i32, f64>,
+}
+
+impl EtaService {
+    pub fn new(config: &EtaConfig, graph_cache: Arc<GraphCache>) -> Self {
+        EtaService {
+            graph_cache,
+            weight_unit_secs: config.weight_unit_secs,
+            area_speed_factors: config.speed_factors(),
+        }
+    }
+
+    // エリアの道路グラフ上で経路を探索し、今出発した場合の到着予定を返す
+    pub async fn estimate_route<T: MapRepository>(
+        &self,
+        map_repository: &T,
+        area_id: i32,
+        from_node_id: i32,
+        to_node_id: i32,
+    ) -> Result<Option<EtaDto>, AppError> {
+        let graph = load_area_graph(map_repository, &self.graph_cache, area_id).await?;
+        let distance = calculate_distance(&graph, from_node_id, to_node_id);
+
+        Ok(self.estimate(area_id, distance, Utc::now()))
+    }
+
+    pub fn travel_time(&self, area_id: i32, distance: i32) -> Duration {
+        let speed_factor = self
+            .area_speed_factors
+            .get(&area_id)
+            .copied()
+            .unwrap_or(1.0);
+        let secs = distance as f64 * self.weight_unit_secs / speed_factor;
+
+        Duration::seconds(secs.round() as i64)
+    }
+
+    // 到達できない経路 (i32::MAX) の場合は None を返す
+    pub fn estimate(
+        &self,
+        area_id: i32,
+        distance: i32,
+        departure_at: DateTime<Utc>,
+    ) -> Option<EtaDto> {
+        if distance == i32::MAX {
+            return None;
+        }
+
+        let travel_time = self.travel_time(area_id, distance);
+        Some(EtaDto {
+            distance,
+            travel_time_secs: travel_time.num_seconds(),
+            estimated_arrival_at: departure_at + travel_time,
+        })
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::errors::AppError;
+
+const MAX_TRACKED_KEYS: usize = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct LoginAttemptPolicy {
+    // この回数まではロックせずに失敗を許容する
+    pub free_attempts: u32,
+    pub base_lockout: Duration,
+    pub max_lockout: Duration,
+    // 最後の失敗からこの時間が経過したら失敗回数をリセットする
+    pub reset_after: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FailedAttempts {
+    count: u32,
+    last_failed_at: Instant,
+    locked_until: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct AttemptTable {
+    policy: LoginAttemptPolicy,
+    entries: Mutex<HashMap<String, FailedAttempts>>,
+}
+
+impl AttemptTable {
+    fn new(policy: LoginAttemptPolicy) -> Self {
+        AttemptTable {
+            policy,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn remaining_lockout(&self, key: &str, now: Instant) -> Option<Duration> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(key)
+            .and_then(|attempts| attempts.locked_until)
+            .filter(|locked_until| *locked_until > now)
+            .map(|locked_until| locked_until - now)
+    }
+
+    fn record_failure(&self, key: &str, now: Instant) {
+        let mut entries = self.entries.lock().unwrap();
+
+        if entries.len() >= MAX_TRACKED_KEYS {
+            let reset_after = self.policy.reset_after;
+            entries.retain(|_, attempts| now - attempts.last_failed_at < reset_after);
+        }
+
+        let attempts = entries.entry(key.to_string()).or_insert(FailedAttempts {
+            count: 0,
+            last_failed_at: now,
+            locked_until: None,
+        });
+        if now - attempts.last_failed_at >= self.policy.reset_after {
+            attempts.count = 0;
+        }
+        attempts.count += 1;
+        attempts.last_failed_at = now;
+
+        if attempts.count > self.policy.free_attempts {
+            // 許容回数を超えた分だけロック時間を倍々に伸ばす
+            let exponent = (attempts.count - self.policy.free_attempts - 1).min(16);
+            let lockout = self
+                .policy
+                .base_lockout
+                .saturating_mul(1 << exponent)
+                .min(self.policy.max_lockout);
+            attempts.locked_until = Some(now + lockout);
+        }
+    }
+
+    fn clear(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+}
+
+#[derive(Debug)]
+pub struct LoginAttemptTracker {
+    by_username: AttemptTable,
+    by_ip: AttemptTable,
+}
+
+impl LoginAttemptTracker {
+    pub fn new(username_policy: LoginAttemptPolicy, ip_policy: LoginAttemptPolicy) -> Self {
+        LoginAttemptTracker {
+            by_username: AttemptTable::new(username_policy),
+            by_ip: AttemptTable::new(ip_policy),
+        }
+    }
+
+    pub fn check(&self, username: &str, client_ip: Option<&str>) -> Result<(), AppError> {
+        let now = Instant::now();
+        let username_lockout = self.by_username.remaining_lockout(username, now);
+        let ip_lockout = client_ip.and_then(|ip| self.by_ip.remaining_lockout(ip, now));
+
+        match username_lockout.max(ip_lockout) {
+            Some(remaining) => Err(AppError::TooManyRequests {
+                retry_after_secs: remaining.as_secs().max(1),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    pub fn record_failure(&self, username: &str, client_ip: Option<&str>) {
+        let now = Instant::now();
+        self.by_username.record_failure(username, now);
+        if let Some(ip) = client_ip {
+            self.by_ip.record_failure(ip, now);
+        }
+    }
+
+    pub fn record_success(&self, username: &str, client_ip: Option<&str>) {
+        self.by_username.clear(username);
+        if let Some(ip) = client_ip {
+            self.by_ip.clear(ip);
+        }
+    }
+}
+
+impl Default for LoginAttemptTracker {
+    fn default() -> Self {
+        LoginAttemptTracker::new(
+            LoginAttemptPolicy {
+                free_attempts: 5,
+                base_lockout: Duration::from_secs(1),
+                max_lockout: Duration::from_secs(15 * 60),
+                reset_after: Duration::from_secs(15 * 60),
+            },
+            LoginAttemptPolicy {
+                free_attempts: 20,
+                base_lockout: Duration::from_secs(1),
+                max_lockout: Duration::from_secs(15 * 60),
+                reset_after: Duration::from_secs(15 * 60),
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> AttemptTable {
+        AttemptTable::new(LoginAttemptPolicy {
+            free_attempts: 2,
+            base_lockout: Duration::from_secs(1),
+            max_lockout: Duration::from_secs(10),
+            reset_after: Duration::from_secs(60),
+        })
+    }
+
+    #[test]
+    fn lockout_starts_after_free_attempts_and_doubles() {
+        let table = table();
+        let now = Instant::now();
+
+        table.record_failure("user", now);
+        table.record_failure("user", now);
+        assert_eq!(table.remaining_lockout("user", now), None);
+
+        table.record_failure("user", now);
+        assert_eq!(
+            table.remaining_lockout("user", now),
+            Some(Duration::from_secs(1))
+        );
+        table.record_failure("user", now);
+        assert_eq!(
+            table.remaining_lockout("user", now),
+            Some(Duration::from_secs(2))
+        );
+        table.record_failure("user", now);
+        assert_eq!(
+            table.remaining_lockout("user", now),
+            Some(Duration::from_secs(4))
+        );
+    }
+
+    #[test]
+    fn lockout_is_capped_and_expires() {
+        let table = table();
+        let now = Instant::now();
+
+        for _ in 0..10 {
+            table.record_failure("user", now);
+        }
+        assert_eq!(
+            table.remaining_lockout("user", now),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            table.remaining_lockout("user", now + Duration::from_secs(10)),
+            None
+        );
+    }
+
+    #[test]
+    fn failures_are_forgotten_after_reset_after() {
+        let table = table();
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            table.record_failure("user", now);
+        }
+        let later = now + Duration::from_secs(60);
+        table.record_failure("user", later);
+
+        assert_eq!(table.remaining_lockout("user", later), None);
+    }
+
+    #[test]
+    fn keys_are_tracked_separately() {
+        let table = table();
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            table.record_failure("user", now);
+        }
+
+        assert!(table.remaining_lockout("user", now).is_some());
+        assert_eq!(table.remaining_lockout("other", now), None);
+    }
+
+    #[test]
+    fn success_clears_username_and_ip_lockouts() {
+        let policy = table().policy;
+        let tracker = LoginAttemptTracker::new(policy.clone(), policy);
+
+        for _ in 0..3 {
+            tracker.record_failure("user", Some("192.0.2.1"));
+        }
+        assert_eq!(
+            tracker.check("user", None).unwrap_err().code(),
+            "too_many_requests"
+        );
+        assert_eq!(
+            tracker
+                .check("other", Some("192.0.2.1"))
+                .unwrap_err()
+                .code(),
+            "too_many_requests"
+        );
+
+        tracker.record_success("user", Some("192.0.2.1"));
+
+        assert!(tracker.check("user", Some("192.0.2.1")).is_ok());
+    }
+}
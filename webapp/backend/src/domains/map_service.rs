@@ -1,40 +1,266 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use tracing::instrument;
+
 use crate::{
     errors::AppError,
-    models::graph::{Edge, Node},
+    infrastructure::graph_cache::GraphCache,
+    models::graph::{Edge, EdgeUpdate, Graph, Node},
 };
 
 pub trait MapRepository {
+    async fn get_all_area_ids(&self) -> Result<Vec<i32>, sqlx::Error>;
     async fn get_all_nodes(&self, area_id: Option<i32>) -> Result<Vec<Node>, sqlx::Error>;
+    // 通行止めの辺は含めない
     async fn get_all_edges(&self, area_id: Option<i32>) -> Result<Vec<Edge>, sqlx::Error>;
     async fn get_area_id_by_node_id(&self, node_id: i32) -> Result<i32, sqlx::Error>;
-    async fn update_edge(
+    // 存在しないノードは結果に含めない
+    async fn get_area_ids_by_node_ids(
         &self,
-        node_a_id: i32,
-        node_b_id: i32,
-        weight: i32,
-    ) -> Result<(), sqlx::Error>;
+        node_ids: &[i32],
+    ) -> Result<Vec<(i32, i32)>, sqlx::Error>;
+    // すべての変更を 1 つのトランザクションで適用し、存在しない辺があれば RowNotFound を返す
+    async fn update_edges(&self, updates: &[EdgeUpdate]) -> Result<(), sqlx::Error>;
+}
+
+async fn build_area_graph<T: MapRepository>(
+    repository: &T,
+    area_id: i32,
+) -> Result<Graph, AppError> {
+    let nodes = repository.get_all_nodes(Some(area_id)).await?;
+    let edges = repository.get_all_edges(Some(area_id)).await?;
+
+    let mut graph = Graph::new();
+    for node in nodes {
+        graph.add_node(node);
+    }
+    for edge in edges {
+        graph.add_edge(edge);
+    }
+
+    Ok(graph)
+}
+
+// キャッシュ済みのグラフがあればそれを使い、なければ DB から構築してキャッシュする
+#[instrument(skip(repository, graph_cache))]
+pub async fn load_area_graph<T: MapRepository>(
+    repository: &T,
+    graph_cache: &GraphCache,
+    area_id: i32,
+) -> Result<Arc<Graph>, AppError> {
+    if let Some(graph) = graph_cache.get(area_id) {
+        return Ok(graph);
+    }
+
+    let generation = graph_cache.generation(area_id);
+    let graph = build_area_graph(repository, area_id).await?;
+    Ok(graph_cache.insert(area_id, generation, graph))
 }
 
 #[derive(Debug)]
 pub struct MapService<T: MapRepository + std::fmt::Debug> {
     repository: T,
+    graph_cache: Arc<GraphCache>,
 }
 
 impl<T: MapRepository + std::fmt::Debug> MapService<T> {
-    pub fn new(repository: T) -> Self {
-        MapService { repository }
+    pub fn new(repository: T, graph_cache: Arc<GraphCache>) -> Self {
+        MapService {
+            repository,
+            graph_cache,
+        }
     }
 
+    // 全エリアのグラフを DB から読み直してキャッシュを置き換える
+    #[instrument(skip(self))]
+    pub async fn refresh_graph_cache(&self) -> Result<(), AppError> {
+        for area_id in self.repository.get_all_area_ids().await? {
+            let generation = self.graph_cache.generation(area_id);
+            let graph = build_area_graph(&self.repository, area_id).await?;
+            self.graph_cache.insert(area_id, generation, graph);
+        }
+        self.graph_cache.mark_warmed();
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
     pub async fn update_edge(
         &self,
         node_a_id: i32,
         node_b_id: i32,
         weight: i32,
     ) -> Result<(), AppError> {
-        self.repository
-            .update_edge(node_a_id, node_b_id, weight)
-            .await?;
+        self.update_edges(vec![EdgeUpdate {
+            node_a_id,
+            node_b_id,
+            weight: Some(weight),
+            is_closed: None,
+        }])
+        .await
+    }
+
+    // 通行止めにした辺は重みを残したまま経路探索から除外する
+    #[instrument(skip(self))]
+    pub async fn set_edge_closed(
+        &self,
+        node_a_id: i32,
+        node_b_id: i32,
+        is_closed: bool,
+    ) -> Result<(), AppError> {
+        self.update_edges(vec![EdgeUpdate {
+            node_a_id,
+            node_b_id,
+            weight: None,
+            is_closed: Some(is_closed),
+        }])
+        .await
+    }
+
+    // 存在しないノードや辺が 1 つでも含まれる場合は何も更新せずに NotFound を返す
+    #[instrument(skip(self, updates), fields(count = updates.len()))]
+    pub async fn update_edges(&self, updates: Vec<EdgeUpdate>) -> Result<(), AppError> {
+        if updates.is_empty()
+            || updates
+                .iter()
+                .any(|update| update.weight.is_none() && update.is_closed.is_none())
+        {
+            return Err(AppError::BadRequest);
+        }
+
+        let node_ids: Vec<i32> = updates
+            .iter()
+            .flat_map(|update| [update.node_a_id, update.node_b_id])
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let node_areas = self.repository.get_area_ids_by_node_ids(&node_ids).await?;
+        if node_areas.len() != node_ids.len() {
+            return Err(AppError::NotFound);
+        }
+        let area_ids: BTreeSet<i32> = node_areas.into_iter().map(|(_, area_id)| area_id).collect();
+
+        match self.repository.update_edges(&updates).await {
+            Ok(()) => {}
+            Err(sqlx::Error::RowNotFound) => return Err(AppError::NotFound),
+            Err(e) => return Err(e.into()),
+        }
+
+        for area_id in area_ids {
+            self.graph_cache.invalidate(area_id);
+        }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::repositories::in_memory::InMemoryMapRepository;
+
+    // エリア 1 に 1 - 2 - 3 の経路、エリア 2 にノード 4 がある
+    fn service() -> (MapService<InMemoryMapRepository>, InMemoryMapRepository) {
+        let repository = InMemoryMapRepository::new(
+            HashMap::from([(1, 1), (2, 1), (3, 1), (4, 2)]),
+            vec![
+                Edge {
+                    node_a_id: 1,
+                    node_b_id: 2,
+                    weight: 5,
+                },
+                Edge {
+                    node_a_id: 2,
+                    node_b_id: 3,
+                    weight: 5,
+                },
+            ],
+        );
+        let service = MapService::new(repository.clone(), Arc::new(GraphCache::new()));
+        (service, repository)
+    }
+
+    fn update(node_a_id: i32, node_b_id: i32, weight: Option<i32>) -> EdgeUpdate {
+        EdgeUpdate {
+            node_a_id,
+            node_b_id,
+            weight,
+            is_closed: None,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn update_edges_applies_changes_and_invalidates_cached_graph() {
+        let (service, repository) = service();
+        let graph = load_area_graph(&repository, &service.graph_cache, 1)
+            .await
+            .unwrap();
+        assert_eq!(graph.shortest_route(1, 3), Some((10, vec![1, 2, 3])));
+
+        service
+            .update_edges(vec![
+                update(2, 1, Some(7)),
+                EdgeUpdate {
+                    is_closed: Some(true),
+                    ..update(2, 3, None)
+                },
+            ])
+            .await
+            .unwrap();
+
+        assert_eq!(repository.edge(1, 2).unwrap().0.weight, 7);
+        assert!(repository.edge(2, 3).unwrap().1);
+        let graph = load_area_graph(&repository, &service.graph_cache, 1)
+            .await
+            .unwrap();
+        assert_eq!(graph.shortest_route(1, 2), Some((7, vec![1, 2])));
+        assert_eq!(graph.shortest_route(1, 3), None);
+    }
+
+    #[actix_rt::test]
+    async fn update_edges_with_unknown_node_changes_nothing() {
+        let (service, repository) = service();
+
+        let err = service
+            .update_edges(vec![update(1, 2, Some(7)), update(3, 99, Some(1))])
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.code(), "not_found");
+        assert_eq!(repository.edge(1, 2).unwrap().0.weight, 5);
+    }
+
+    #[actix_rt::test]
+    async fn update_edges_with_unknown_edge_rolls_back_earlier_updates() {
+        let (service, repository) = service();
+        let graph_cache = service.graph_cache.clone();
+        load_area_graph(&repository, &graph_cache, 1).await.unwrap();
+
+        // ノードは存在するが 1 - 3 を直接結ぶ辺はない
+        let err = service
+            .update_edges(vec![update(1, 2, Some(7)), update(1, 3, Some(1))])
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.code(), "not_found");
+        assert_eq!(repository.edge(1, 2).unwrap().0.weight, 5);
+        // 何も変わっていないのでキャッシュは捨てない
+        assert!(graph_cache.get(1).is_some());
+    }
+
+    #[actix_rt::test]
+    async fn update_edges_rejects_empty_or_no_op_updates() {
+        let (service, _) = service();
+
+        let err = service.update_edges(vec![]).await.unwrap_err();
+        assert_eq!(err.code(), "bad_request");
+        let err = service
+            .update_edges(vec![update(1, 2, None)])
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "bad_request");
+    }
+}
@@ -1,5 +1,11 @@
 pub mod auth_service;
+pub mod driver_service;
 pub mod dto;
+pub mod eta_service;
+pub mod login_attempt_tracker;
 pub mod map_service;
+pub mod order_events;
 pub mod order_service;
+pub mod profile_image;
+pub mod tow_truck_events;
 pub mod tow_truck_service;
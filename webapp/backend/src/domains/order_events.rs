@@ -0,0 +1,158 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::models::order::Order;
+
+// 購読者の受信が追いつかない場合に保持しておくイベント数
+const ORDER_EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderEventKind {
+    Created,
+    Dispatched,
+    StatusChanged,
+    Cancelled,
+}
+
+impl OrderEventKind {
+    // SSE の event フィールドに使う名前
+    pub fn event_name(&self) -> &'static str {
+        match self {
+            OrderEventKind::Created => "order_created",
+            OrderEventKind::Dispatched => "order_dispatched",
+            OrderEventKind::StatusChanged => "order_status_changed",
+            OrderEventKind::Cancelled => "order_cancelled",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderEvent {
+    pub kind: OrderEventKind,
+    pub order_id: i32,
+    pub client_id: i32,
+    pub area_id: i32,
+    pub status: String,
+    pub tow_truck_id: Option<i32>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+impl OrderEvent {
+    // 更新後のオーダーと、その所属エリアからイベントを組み立てる
+    pub fn new(kind: OrderEventKind, order: &Order, area_id: i32) -> Self {
+        OrderEvent {
+            kind,
+            order_id: order.id,
+            client_id: order.client_id,
+            area_id,
+            status: order.status.clone(),
+            tow_truck_id: order.tow_truck_id,
+            occurred_at: Utc::now(),
+        }
+    }
+}
+
+// 購読者ごとに受け取るイベントの範囲
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderEventScope {
+    All,
+    Area(i32),
+    Client(i32),
+}
+
+impl OrderEventScope {
+    pub fn includes(&self, event: &OrderEvent) -> bool {
+        match self {
+            OrderEventScope::All => true,
+            OrderEventScope::Area(area_id) => event.area_id == *area_id,
+            OrderEventScope::Client(client_id) => event.client_id == *client_id,
+        }
+    }
+}
+
+// OrderService の更新処理から発行されたイベントを購読者に配信する
+#[derive(Debug)]
+pub struct OrderEventBus {
+    sender: broadcast::Sender<OrderEvent>,
+}
+
+impl OrderEventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(ORDER_EVENT_CHANNEL_CAPACITY);
+        OrderEventBus { sender }
+    }
+
+    pub fn publish(&self, event: OrderEvent) {
+        // 購読者がいない場合の送信エラーは無視してよい
+        let _ = self.sender.send(event);
+    }
+
+    // 購読者がいなければ、イベントを組み立てるための読み込みを省ける
+    pub fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<OrderEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for OrderEventBus {
+    fn default() -> Self {
+        OrderEventBus::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(client_id: i32, area_id: i32) -> OrderEvent {
+        OrderEvent {
+            kind: OrderEventKind::Created,
+            order_id: 1,
+            client_id,
+            area_id,
+            status: "pending".to_string(),
+            tow_truck_id: None,
+            occurred_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn all_scope_includes_every_event() {
+        assert!(OrderEventScope::All.includes(&event(10, 1)));
+        assert!(OrderEventScope::All.includes(&event(11, 2)));
+    }
+
+    #[test]
+    fn area_scope_includes_only_events_in_the_area() {
+        let scope = OrderEventScope::Area(1);
+
+        assert!(scope.includes(&event(10, 1)));
+        assert!(!scope.includes(&event(10, 2)));
+    }
+
+    #[test]
+    fn client_scope_includes_only_own_orders() {
+        let scope = OrderEventScope::Client(10);
+
+        assert!(scope.includes(&event(10, 1)));
+        assert!(scope.includes(&event(10, 2)));
+        assert!(!scope.includes(&event(11, 1)));
+    }
+
+    #[test]
+    fn has_subscribers_follows_receivers() {
+        let bus = OrderEventBus::new();
+        assert!(!bus.has_subscribers());
+
+        let receiver = bus.subscribe();
+        assert!(bus.has_subscribers());
+
+        drop(receiver);
+        assert!(!bus.has_subscribers());
+    }
+}
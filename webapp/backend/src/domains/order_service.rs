@@ -1,10 +1,26 @@
+use std::sync::Arc;
+
 use chrono::{DateTime, Utc};
+use log::warn;
+use tokio::sync::broadcast;
+use tracing::instrument;
 
 use super::{
-    auth_service::AuthRepository, dto::order::OrderDto, map_service::MapRepository,
-    tow_truck_service::TowTruckRepository,
+    auth_service::{AuthRepository, ADMIN_ROLE},
+    dto::eta::EtaDto,
+    dto::order::{OrderDto, OrderTrackingDto, TrackingDriverDto, TrackingTowTruckDto},
+    dto::validators::ALLOWED_ORDER_STATUSES,
+    eta_service::EtaService,
+    map_service::MapRepository,
+    order_events::{OrderEvent, OrderEventBus, OrderEventKind, OrderEventScope},
+    tow_truck_events::{TowTruckEvent, TowTruckEventBus, TowTruckEventKind},
+    tow_truck_service::{publish_tow_truck_event, TowTruckRepository},
+};
+use crate::{
+    errors::AppError,
+    infrastructure::metrics::metrics,
+    models::{order::Order, tow_truck::TowTruck},
 };
-use crate::{errors::AppError, models::order::Order};
 
 pub trait OrderRepository {
     async fn find_order_by_id(&self, id: i32) -> Result<Order, AppError>;
@@ -23,13 +39,41 @@ pub trait OrderRepository {
         customer_id: i32,
         node_id: i32,
         car_value: f64,
-    ) -> Result<(), AppError>;
+    ) -> Result<i32, AppError>;
     async fn update_order_dispatched(
         &self,
         id: i32,
         dispatcher_id: i32,
         tow_truck_id: i32,
     ) -> Result<(), AppError>;
+    async fn get_orders_by_client_id(
+        &self,
+        client_id: i32,
+        statuses: &[String],
+        page: i32,
+        page_size: i32,
+    ) -> Result<Vec<Order>, AppError>;
+    async fn find_active_order_by_tow_truck_id(
+        &self,
+        tow_truck_id: i32,
+    ) -> Result<Option<Order>, AppError>;
+    async fn get_orders_by_tow_truck_id(
+        &self,
+        tow_truck_id: i32,
+        page: i32,
+        page_size: i32,
+    ) -> Result<Vec<Order>, AppError>;
+    async fn advance_order_status(
+        &self,
+        order_id: i32,
+        current_status: &str,
+        next_status: &str,
+    ) -> Result<bool, AppError>;
+    async fn update_order_completed(
+        &self,
+        order_id: i32,
+        completed_time: DateTime<Utc>,
+    ) -> Result<bool, AppError>;
     async fn create_completed_order(
         &self,
         order_id: i32,
@@ -49,6 +93,9 @@ pub struct OrderService<
     tow_truck_repository: U,
     auth_repository: V,
     map_repository: W,
+    events: Arc<OrderEventBus>,
+    tow_truck_events: Arc<TowTruckEventBus>,
+    eta_service: Arc<EtaService>,
 }
 
 impl<
@@ -63,76 +110,290 @@ impl<
         tow_truck_repository: U,
         auth_repository: V,
         map_repository: W,
+        events: Arc<OrderEventBus>,
+        tow_truck_events: Arc<TowTruckEventBus>,
+        eta_service: Arc<EtaService>,
     ) -> Self {
         OrderService {
             order_repository,
             tow_truck_repository,
             auth_repository,
             map_repository,
+            events,
+            tow_truck_events,
+            eta_service,
         }
     }
 
+    #[instrument(skip(self))]
     pub async fn update_order_status(&self, order_id: i32, status: &str) -> Result<(), AppError> {
         self.order_repository
             .update_order_status(order_id, status)
-            .await
+            .await?;
+
+        let kind = match status {
+            "cancelled" => OrderEventKind::Cancelled,
+            _ => OrderEventKind::StatusChanged,
+        };
+        self.publish_order_event(kind, order_id).await;
+
+        Ok(())
     }
 
+    // ユーザーのロールに応じた範囲でオーダーのイベントを購読する
+    pub async fn subscribe_order_events(
+        &self,
+        user_id: i32,
+    ) -> Result<(OrderEventScope, broadcast::Receiver<OrderEvent>), AppError> {
+        let user = match self.auth_repository.find_user_by_id(user_id).await? {
+            Some(user) => user,
+            None => return Err(AppError::Unauthorized),
+        };
+
+        let scope = match user.role.as_str() {
+            ADMIN_ROLE => OrderEventScope::All,
+            "dispatcher" => match self
+                .auth_repository
+                .find_dispatcher_by_user_id(user_id)
+                .await?
+            {
+                Some(dispatcher) => OrderEventScope::Area(dispatcher.area_id),
+                None => return Err(AppError::Forbidden),
+            },
+            "client" => OrderEventScope::Client(user_id),
+            _ => return Err(AppError::Forbidden),
+        };
+
+        Ok((scope, self.events.subscribe()))
+    }
+
+    async fn publish_order_event(&self, kind: OrderEventKind, order_id: i32) {
+        publish_order_event(
+            &self.order_repository,
+            &self.map_repository,
+            &self.events,
+            kind,
+            order_id,
+        )
+        .await;
+    }
+
+    #[instrument(skip(self))]
     pub async fn get_order_by_id(&self, id: i32) -> Result<OrderDto, AppError> {
         let order = self.order_repository.find_order_by_id(id).await?;
+        let mut dto = self.to_order_dto(order.clone()).await?;
 
-        let client_username = self
-            .auth_repository
-            .find_user_by_id(order.client_id)
+        // 一覧では経路探索の負荷が大きいため、到着予定は詳細でだけ返す
+        if let Some(tow_truck_id) = order.tow_truck_id {
+            if let Some(tow_truck) = self
+                .tow_truck_repository
+                .find_tow_truck_by_id(tow_truck_id)
+                .await?
+            {
+                dto.eta = self
+                    .estimate_arrival(&order, &tow_truck, dto.area_id)
+                    .await?;
+            }
+        }
+
+        Ok(dto)
+    }
+
+    // 依頼者向けにオーダーの進捗と、向かっているレッカー車の到着予定を返す
+    #[instrument(skip(self))]
+    pub async fn track_client_order(
+        &self,
+        user_id: i32,
+        order_id: i32,
+    ) -> Result<OrderTrackingDto, AppError> {
+        let order = self.order_repository.find_order_by_id(order_id).await?;
+        // 他のクライアントのオーダーは存在しないものとして扱う
+        if order.client_id != user_id {
+            return Err(AppError::NotFound);
+        }
+
+        let area_id = self
+            .map_repository
+            .get_area_id_by_node_id(order.node_id)
+            .await?;
+        let tow_truck = match order.tow_truck_id {
+            Some(tow_truck_id) => {
+                self.tow_truck_repository
+                    .find_tow_truck_by_id(tow_truck_id)
+                    .await?
+            }
+            None => None,
+        };
+
+        let eta = match &tow_truck {
+            Some(tow_truck) => self.estimate_arrival(&order, tow_truck, area_id).await?,
+            None => None,
+        };
+
+        Ok(OrderTrackingDto {
+            order_id: order.id,
+            status: order.status,
+            node_id: order.node_id,
+            area_id,
+            order_time: order.order_time,
+            completed_time: order.completed_time,
+            driver: tow_truck.as_ref().map(|tow_truck| TrackingDriverDto {
+                user_id: tow_truck.driver_id,
+                username: tow_truck.driver_username.clone(),
+            }),
+            tow_truck: tow_truck.map(|tow_truck| TrackingTowTruckDto {
+                id: tow_truck.id,
+                status: tow_truck.status,
+                node_id: tow_truck.node_id,
+            }),
+            eta,
+        })
+    }
+
+    // ログイン中のクライアントのオーダーを新しい順に返す (statuses が空の場合はすべて)
+    #[instrument(skip(self))]
+    pub async fn get_client_orders(
+        &self,
+        client_id: i32,
+        statuses: Vec<String>,
+        page: i32,
+        page_size: i32,
+    ) -> Result<Vec<OrderDto>, AppError> {
+        if page < 0 || page_size < 1 {
+            return Err(AppError::BadRequest);
+        }
+        if statuses
+            .iter()
+            .any(|status| !ALLOWED_ORDER_STATUSES.contains(&status.as_str()))
+        {
+            return Err(AppError::BadRequest);
+        }
+
+        let orders = self
+            .order_repository
+            .get_orders_by_client_id(client_id, &statuses, page, page_size)
+            .await?;
+
+        let mut results = Vec::new();
+        for order in orders {
+            results.push(self.to_order_dto(order).await?);
+        }
+
+        Ok(results)
+    }
+
+    // 過去のオーダーと同じ地点・車両価格で新しいオーダーを作成する
+    #[instrument(skip(self))]
+    pub async fn repeat_client_order(
+        &self,
+        client_id: i32,
+        order_id: i32,
+    ) -> Result<i32, AppError> {
+        let order = self.order_repository.find_order_by_id(order_id).await?;
+        // 他のクライアントのオーダーは存在しないものとして扱う
+        if order.client_id != client_id {
+            return Err(AppError::NotFound);
+        }
+
+        self.create_client_order(client_id, order.node_id, order.car_value)
             .await
-            .unwrap()
-            .unwrap()
-            .username;
+    }
+
+    // レッカー車が依頼者の地点へ向かっている間だけ到着予定を計算する
+    async fn estimate_arrival(
+        &self,
+        order: &Order,
+        tow_truck: &TowTruck,
+        area_id: i32,
+    ) -> Result<Option<EtaDto>, AppError> {
+        if order.status != "dispatched" {
+            return Ok(None);
+        }
+
+        self.eta_service
+            .estimate_route(
+                &self.map_repository,
+                area_id,
+                tow_truck.node_id,
+                order.node_id,
+            )
+            .await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn get_paginated_orders(
+        &self,
+        page: i32,
+        page_size: i32,
+        sort_by: Option<String>,
+        sort_order: Option<String>,
+        status: Option<String>,
+        area: Option<i32>,
+    ) -> Result<Vec<OrderDto>, AppError> {
+        let orders = self
+            .order_repository
+            .get_paginated_orders(page, page_size, sort_by, sort_order, status, area)
+            .await?;
+
+        let mut results = Vec::new();
+
+        for order in orders {
+            results.push(self.to_order_dto(order).await?);
+        }
+
+        Ok(results)
+    }
+
+    // 参照先のユーザー・ディスパッチャ・レッカー車が存在しない場合は None として扱う
+    async fn to_order_dto(&self, order: Order) -> Result<OrderDto, AppError> {
+        let client_username = self.find_username(order.client_id, order.id).await?;
 
         let dispatcher = match order.dispatcher_id {
-            Some(dispatcher_id) => self
-                .auth_repository
-                .find_dispatcher_by_id(dispatcher_id)
-                .await
-                .unwrap(),
+            Some(dispatcher_id) => {
+                let dispatcher = self
+                    .auth_repository
+                    .find_dispatcher_by_id(dispatcher_id)
+                    .await?;
+                if dispatcher.is_none() {
+                    warn!(
+                        "dispatcher {} referenced by order {} was not found",
+                        dispatcher_id, order.id
+                    );
+                }
+                dispatcher
+            }
             None => None,
         };
 
         let (dispatcher_user_id, dispatcher_username) = match dispatcher {
             Some(dispatcher) => (
                 Some(dispatcher.user_id),
-                Some(
-                    self.auth_repository
-                        .find_user_by_id(dispatcher.user_id)
-                        .await
-                        .unwrap()
-                        .unwrap()
-                        .username,
-                ),
+                self.find_username(dispatcher.user_id, order.id).await?,
             ),
             None => (None, None),
         };
 
         let tow_truck = match order.tow_truck_id {
-            Some(tow_truck_id) => self
-                .tow_truck_repository
-                .find_tow_truck_by_id(tow_truck_id)
-                .await
-                .unwrap(),
+            Some(tow_truck_id) => {
+                let tow_truck = self
+                    .tow_truck_repository
+                    .find_tow_truck_by_id(tow_truck_id)
+                    .await?;
+                if tow_truck.is_none() {
+                    warn!(
+                        "tow truck {} referenced by order {} was not found",
+                        tow_truck_id, order.id
+                    );
+                }
+                tow_truck
+            }
             None => None,
         };
 
         let (driver_user_id, driver_username) = match tow_truck {
             Some(tow_truck) => (
                 Some(tow_truck.driver_id),
-                Some(
-                    self.auth_repository
-                        .find_user_by_id(tow_truck.driver_id)
-                        .await
-                        .unwrap()
-                        .unwrap()
-                        .username,
-                ),
+                self.find_username(tow_truck.driver_id, order.id).await?,
             ),
             None => (None, None),
         };
@@ -140,145 +401,78 @@ impl<
         let area_id = self
             .map_repository
             .get_area_id_by_node_id(order.node_id)
-            .await
-            .unwrap();
+            .await?;
 
         Ok(OrderDto {
             id: order.id,
             client_id: order.client_id,
-            client_username: Some(client_username),
+            client_username,
+            dispatcher_id: order.dispatcher_id,
             dispatcher_user_id,
             dispatcher_username,
+            tow_truck_id: order.tow_truck_id,
             driver_user_id,
             driver_username,
             area_id,
-            dispatcher_id: order.dispatcher_id,
-            tow_truck_id: order.tow_truck_id,
             status: order.status,
             node_id: order.node_id,
             car_value: order.car_value,
             order_time: order.order_time,
             completed_time: order.completed_time,
+            eta: None,
         })
     }
 
-    pub async fn get_paginated_orders(
-        &self,
-        page: i32,
-        page_size: i32,
-        sort_by: Option<String>,
-        sort_order: Option<String>,
-        status: Option<String>,
-        area: Option<i32>,
-    ) -> Result<Vec<OrderDto>, AppError> {
-        let orders = self
-            .order_repository
-            .get_paginated_orders(page, page_size, sort_by, sort_order, status, area)
-            .await?;
-
-        let mut results = Vec::new();
-
-        for order in orders {
-            let client_username = self
-                .auth_repository
-                .find_user_by_id(order.client_id)
-                .await
-                .unwrap()
-                .unwrap()
-                .username;
-
-            let dispatcher = match order.dispatcher_id {
-                Some(dispatcher_id) => self
-                    .auth_repository
-                    .find_dispatcher_by_id(dispatcher_id)
-                    .await
-                    .unwrap(),
-                None => None,
-            };
-
-            let (dispatcher_user_id, dispatcher_username) = match dispatcher {
-                Some(dispatcher) => (
-                    Some(dispatcher.user_id),
-                    Some(
-                        self.auth_repository
-                            .find_user_by_id(dispatcher.user_id)
-                            .await
-                            .unwrap()
-                            .unwrap()
-                            .username,
-                    ),
-                ),
-                None => (None, None),
-            };
-
-            let tow_truck = match order.tow_truck_id {
-                Some(tow_truck_id) => self
-                    .tow_truck_repository
-                    .find_tow_truck_by_id(tow_truck_id)
-                    .await
-                    .unwrap(),
-                None => None,
-            };
-
-            let (driver_user_id, driver_username) = match tow_truck {
-                Some(tow_truck) => (
-                    Some(tow_truck.driver_id),
-                    Some(
-                        self.auth_repository
-                            .find_user_by_id(tow_truck.driver_id)
-                            .await
-                            .unwrap()
-                            .unwrap()
-                            .username,
-                    ),
-                ),
-                None => (None, None),
-            };
-
-            let order_area_id = self
-                .map_repository
-                .get_area_id_by_node_id(order.node_id)
-                .await
-                .unwrap();
-
-            results.push(OrderDto {
-                id: order.id,
-                client_id: order.client_id,
-                client_username: Some(client_username),
-                dispatcher_id: order.dispatcher_id,
-                dispatcher_user_id,
-                dispatcher_username,
-                tow_truck_id: order.tow_truck_id,
-                driver_user_id,
-                driver_username,
-                area_id: order_area_id,
-                status: order.status,
-                node_id: order.node_id,
-                car_value: order.car_value,
-                order_time: order.order_time,
-                completed_time: order.completed_time,
-            });
+    async fn find_username(&self, user_id: i32, order_id: i32) -> Result<Option<String>, AppError> {
+        match self.auth_repository.find_user_by_id(user_id).await? {
+            Some(user) => Ok(Some(user.username)),
+            None => {
+                warn!(
+                    "user {} referenced by order {} was not found",
+                    user_id, order_id
+                );
+                Ok(None)
+            }
         }
-
-        Ok(results)
     }
 
+    #[instrument(skip(self))]
     pub async fn create_client_order(
         &self,
         client_id: i32,
         node_id: i32,
         car_value: f64,
-    ) -> Result<(), AppError> {
-        match self
+    ) -> Result<i32, AppError> {
+        let order_id = self
             .order_repository
             .create_order(client_id, node_id, car_value)
-            .await
-        {
-            Ok(_) => Ok(()),
-            Err(_) => Err(AppError::BadRequest),
+            .await?;
+
+        // 作成したばかりのオーダーは読み直さず、エリアだけを引いてイベントを組み立てる
+        if self.events.has_subscribers() {
+            match self.map_repository.get_area_id_by_node_id(node_id).await {
+                Ok(area_id) => self.events.publish(OrderEvent {
+                    kind: OrderEventKind::Created,
+                    order_id,
+                    client_id,
+                    area_id,
+                    status: "pending".to_string(),
+                    tow_truck_id: None,
+                    occurred_at: Utc::now(),
+                }),
+                Err(e) => warn!(
+                    "failed to resolve area of order {} for {:?} event: {:?}",
+                    order_id,
+                    OrderEventKind::Created,
+                    e
+                ),
+            }
         }
+
+        Ok(order_id)
     }
 
+    #[instrument(skip(self))]
     pub async fn create_dispatcher_order(
         &self,
         order_id: i32,
@@ -286,15 +480,79 @@ impl<
         tow_truck_id: i32,
         order_time: DateTime<Utc>,
     ) -> Result<(), AppError> {
-        if (self
-            .order_repository
-            .create_completed_order(order_id, tow_truck_id, order_time)
-            .await)
-            .is_err()
+        let result = self
+            .dispatch_order(order_id, dispatcher_id, tow_truck_id, order_time)
+            .await;
+        metrics().record_dispatch(result.is_ok());
+
+        // 配車前に読み込んだレッカー車から、読み直さずにイベントを組み立てる
+        match result? {
+            Some(mut tow_truck) => {
+                if self.events.has_subscribers() {
+                    self.publish_dispatched_event(order_id, tow_truck.area_id)
+                        .await;
+                }
+                tow_truck.status = "busy".to_string();
+                self.tow_truck_events.publish(TowTruckEvent::new(
+                    TowTruckEventKind::StatusChanged,
+                    tow_truck,
+                ));
+            }
+            None => {
+                self.publish_order_event(OrderEventKind::Dispatched, order_id)
+                    .await;
+                publish_tow_truck_event(
+                    &self.tow_truck_repository,
+                    &self.tow_truck_events,
+                    TowTruckEventKind::StatusChanged,
+                    tow_truck_id,
+                )
+                .await;
+            }
+        }
+
+        Ok(())
+    }
+
+    // 配車されたオーダーはレッカー車と同じエリアにあるため、エリアの問い合わせは省く
+    async fn publish_dispatched_event(&self, order_id: i32, area_id: i32) {
+        match self.order_repository.find_order_by_id(order_id).await {
+            Ok(order) => {
+                self.events
+                    .publish(OrderEvent::new(OrderEventKind::Dispatched, &order, area_id))
+            }
+            Err(e) => warn!(
+                "failed to load order {} for {:?} event: {:?}",
+                order_id,
+                OrderEventKind::Dispatched,
+                e
+            ),
+        }
+    }
+
+    async fn dispatch_order(
+        &self,
+        order_id: i32,
+        dispatcher_id: i32,
+        tow_truck_id: i32,
+        order_time: DateTime<Utc>,
+    ) -> Result<Option<TowTruck>, AppError> {
+        // 勤務外のレッカー車には配車しない
+        let tow_truck = self
+            .tow_truck_repository
+            .find_tow_truck_by_id(tow_truck_id)
+            .await?;
+        if tow_truck
+            .as_ref()
+            .is_some_and(|tow_truck| tow_truck.status == "off_duty")
         {
-            return Err(AppError::BadRequest);
+            return Err(AppError::Conflict);
         }
 
+        self.order_repository
+            .create_completed_order(order_id, tow_truck_id, order_time)
+            .await?;
+
         self.order_repository
             .update_order_dispatched(order_id, dispatcher_id, tow_truck_id)
             .await?;
@@ -303,6 +561,393 @@ impl<
             .update_status(tow_truck_id, "busy")
             .await?;
 
-        Ok(())
+        Ok(tow_truck)
+    }
+}
+
+// 更新後のオーダーを読み直して購読者に配信する
+// 更新前に読み込んだ値から組み立てられる場合は OrderEvent::new を直接使う
+// 配信に失敗しても更新処理自体は成功として扱う
+pub async fn publish_order_event<T: OrderRepository, W: MapRepository>(
+    order_repository: &T,
+    map_repository: &W,
+    events: &OrderEventBus,
+    kind: OrderEventKind,
+    order_id: i32,
+) {
+    if !events.has_subscribers() {
+        return;
+    }
+
+    let order = match order_repository.find_order_by_id(order_id).await {
+        Ok(order) => order,
+        Err(e) => {
+            warn!(
+                "failed to load order {} for {:?} event: {:?}",
+                order_id, kind, e
+            );
+            return;
+        }
+    };
+    let area_id = match map_repository.get_area_id_by_node_id(order.node_id).await {
+        Ok(area_id) => area_id,
+        Err(e) => {
+            warn!(
+                "failed to resolve area of order {} for {:?} event: {:?}",
+                order_id, kind, e
+            );
+            return;
+        }
+    };
+
+    events.publish(OrderEvent::new(kind, &order, area_id));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+    use crate::config::EtaConfig;
+    use crate::infrastructure::graph_cache::GraphCache;
+    use crate::models::{
+        graph::Edge,
+        user::{Dispatcher, User},
+    };
+    use crate::repositories::in_memory::{
+        InMemoryAuthRepository, InMemoryMapRepository, InMemoryOrderRepository,
+        InMemoryTowTruckRepository,
+    };
+
+    type TestOrderService = OrderService<
+        InMemoryOrderRepository,
+        InMemoryTowTruckRepository,
+        InMemoryAuthRepository,
+        InMemoryMapRepository,
+    >;
+
+    fn user(id: i32, username: &str, role: &str) -> User {
+        User {
+            id,
+            username: username.to_string(),
+            password: String::new(),
+            profile_image: "default.png".to_string(),
+            role: role.to_string(),
+        }
+    }
+
+    fn order(
+        id: i32,
+        client_id: i32,
+        dispatcher_id: Option<i32>,
+        tow_truck_id: Option<i32>,
+    ) -> Order {
+        Order {
+            id,
+            client_id,
+            dispatcher_id,
+            tow_truck_id,
+            status: "dispatched".to_string(),
+            node_id: 1,
+            car_value: 1000.0,
+            order_time: Utc.with_ymd_and_hms(2024, 7, 25, 1, 0, 0).unwrap(),
+            completed_time: None,
+        }
+    }
+
+    fn service(orders: Vec<Order>) -> TestOrderService {
+        OrderService::new(
+            InMemoryOrderRepository::new(orders),
+            InMemoryTowTruckRepository::new(vec![TowTruck {
+                id: 1,
+                driver_id: 30,
+                driver_username: None,
+                status: "busy".to_string(),
+                area_id: 1,
+                node_id: 1,
+            }]),
+            InMemoryAuthRepository::new(
+                vec![
+                    user(10, "client", "client"),
+                    user(20, "dispatcher", "dispatcher"),
+                    user(30, "driver", "driver"),
+                ],
+                vec![
+                    Dispatcher {
+                        id: 1,
+                        user_id: 20,
+                        area_id: 1,
+                    },
+                    // ユーザーが削除されたディスパッチャ
+                    Dispatcher {
+                        id: 2,
+                        user_id: 99,
+                        area_id: 1,
+                    },
+                ],
+            ),
+            InMemoryMapRepository::new(
+                HashMap::from([(1, 1), (2, 1)]),
+                vec![Edge {
+                    node_a_id: 1,
+                    node_b_id: 2,
+                    weight: 5,
+                }],
+            ),
+            Arc::new(OrderEventBus::new()),
+            Arc::new(TowTruckEventBus::new()),
+            Arc::new(EtaService::new(
+                &EtaConfig {
+                    weight_unit_secs: 60.0,
+                    area_speed_factors: HashMap::from([("1".to_string(), 2.0)]),
+                },
+                Arc::new(GraphCache::new()),
+            )),
+        )
+    }
+
+    #[actix_rt::test]
+    async fn get_order_by_id_resolves_related_usernames() {
+        let service = service(vec![order(1, 10, Some(1), Some(1))]);
+
+        let dto = service.get_order_by_id(1).await.unwrap();
+
+        assert_eq!(dto.client_username.as_deref(), Some("client"));
+        assert_eq!(dto.dispatcher_user_id, Some(20));
+        assert_eq!(dto.dispatcher_username.as_deref(), Some("dispatcher"));
+        assert_eq!(dto.driver_user_id, Some(30));
+        assert_eq!(dto.driver_username.as_deref(), Some("driver"));
+        assert_eq!(dto.area_id, 1);
+    }
+
+    #[actix_rt::test]
+    async fn get_order_by_id_tolerates_missing_rows() {
+        let service = service(vec![order(1, 404, Some(404), Some(404))]);
+
+        let dto = service.get_order_by_id(1).await.unwrap();
+
+        assert_eq!(dto.client_username, None);
+        assert_eq!(dto.dispatcher_id, Some(404));
+        assert_eq!(dto.dispatcher_user_id, None);
+        assert_eq!(dto.dispatcher_username, None);
+        assert_eq!(dto.tow_truck_id, Some(404));
+        assert_eq!(dto.driver_user_id, None);
+        assert_eq!(dto.driver_username, None);
+    }
+
+    #[actix_rt::test]
+    async fn get_order_by_id_tolerates_dispatcher_without_user() {
+        let service = service(vec![order(1, 10, Some(2), None)]);
+
+        let dto = service.get_order_by_id(1).await.unwrap();
+
+        assert_eq!(dto.dispatcher_user_id, Some(99));
+        assert_eq!(dto.dispatcher_username, None);
+    }
+
+    #[actix_rt::test]
+    async fn get_order_by_id_returns_not_found_for_unknown_order() {
+        let service = service(vec![]);
+
+        let err = service.get_order_by_id(1).await.unwrap_err();
+
+        assert_eq!(err.code(), "not_found");
+    }
+
+    #[actix_rt::test]
+    async fn get_paginated_orders_keeps_orders_with_dangling_references() {
+        let service = service(vec![
+            order(1, 10, Some(1), Some(1)),
+            order(2, 404, Some(404), Some(404)),
+        ]);
+
+        let dtos = service
+            .get_paginated_orders(0, 10, None, None, None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(dtos.len(), 2);
+        assert_eq!(dtos[0].client_username.as_deref(), Some("client"));
+        assert_eq!(dtos[1].client_username, None);
+        assert_eq!(dtos[1].driver_username, None);
+    }
+
+    #[actix_rt::test]
+    async fn track_client_order_returns_eta_while_dispatched() {
+        let mut dispatched = order(1, 10, Some(1), Some(1));
+        dispatched.node_id = 2;
+        let service = service(vec![dispatched]);
+
+        let dto = service.track_client_order(10, 1).await.unwrap();
+
+        assert_eq!(dto.status, "dispatched");
+        assert_eq!(dto.driver.as_ref().map(|d| d.user_id), Some(30));
+        assert_eq!(dto.tow_truck.as_ref().map(|t| t.node_id), Some(1));
+        let eta = dto.eta.unwrap();
+        assert_eq!(eta.distance, 5);
+        // 重み 1 = 60 秒、エリア 1 の速度係数 2.0
+        assert_eq!(eta.travel_time_secs, 150);
+    }
+
+    #[actix_rt::test]
+    async fn track_client_order_omits_eta_before_dispatch() {
+        let mut pending = order(1, 10, None, None);
+        pending.status = "pending".to_string();
+        let service = service(vec![pending]);
+
+        let dto = service.track_client_order(10, 1).await.unwrap();
+
+        assert!(dto.driver.is_none());
+        assert!(dto.tow_truck.is_none());
+        assert!(dto.eta.is_none());
+    }
+
+    #[actix_rt::test]
+    async fn track_client_order_hides_other_clients_orders() {
+        let service = service(vec![order(1, 10, Some(1), Some(1))]);
+
+        let err = service.track_client_order(11, 1).await.unwrap_err();
+
+        assert_eq!(err.code(), "not_found");
+    }
+
+    #[actix_rt::test]
+    async fn get_client_orders_returns_only_own_orders_with_status() {
+        let mut pending = order(3, 10, None, None);
+        pending.status = "pending".to_string();
+        let service = service(vec![
+            order(1, 10, Some(1), Some(1)),
+            order(2, 11, Some(1), Some(1)),
+            pending,
+        ]);
+
+        let dtos = service
+            .get_client_orders(10, vec!["dispatched".to_string()], 0, 10)
+            .await
+            .unwrap();
+
+        assert_eq!(dtos.iter().map(|dto| dto.id).collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[actix_rt::test]
+    async fn get_client_orders_rejects_unknown_status() {
+        let service = service(vec![]);
+
+        let err = service
+            .get_client_orders(10, vec!["unknown".to_string()], 0, 10)
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.code(), "bad_request");
+    }
+
+    #[actix_rt::test]
+    async fn repeat_client_order_hides_other_clients_orders() {
+        let service = service(vec![order(1, 10, Some(1), Some(1))]);
+
+        let err = service.repeat_client_order(11, 1).await.unwrap_err();
+
+        assert_eq!(err.code(), "not_found");
+    }
+
+    #[actix_rt::test]
+    async fn create_client_order_publishes_created_event() {
+        let service = service(vec![]);
+        let mut events = service.events.subscribe();
+
+        let order_id = service.create_client_order(10, 2, 500.0).await.unwrap();
+
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.kind, OrderEventKind::Created);
+        assert_eq!(event.order_id, order_id);
+        assert_eq!(event.client_id, 10);
+        assert_eq!(event.area_id, 1);
+        assert_eq!(event.status, "pending");
+        assert_eq!(event.tow_truck_id, None);
+    }
+
+    #[actix_rt::test]
+    async fn create_dispatcher_order_publishes_order_and_tow_truck_events() {
+        let mut pending = order(1, 10, None, None);
+        pending.status = "pending".to_string();
+        let service = service(vec![pending]);
+        service
+            .tow_truck_repository
+            .update_status(1, "available")
+            .await
+            .unwrap();
+        let mut order_events = service.events.subscribe();
+        let mut tow_truck_events = service.tow_truck_events.subscribe();
+
+        service
+            .create_dispatcher_order(1, 1, 1, Utc::now())
+            .await
+            .unwrap();
+
+        let event = order_events.try_recv().unwrap();
+        assert_eq!(event.kind, OrderEventKind::Dispatched);
+        assert_eq!(event.client_id, 10);
+        assert_eq!(event.area_id, 1);
+        assert_eq!(event.status, "dispatched");
+        assert_eq!(event.tow_truck_id, Some(1));
+        let event = tow_truck_events.try_recv().unwrap();
+        assert_eq!(event.kind, TowTruckEventKind::StatusChanged);
+        assert_eq!(event.tow_truck.id, 1);
+        assert_eq!(event.tow_truck.status, "busy");
+    }
+
+    #[actix_rt::test]
+    async fn create_dispatcher_order_rejects_off_duty_tow_truck_without_events() {
+        let service = service(vec![order(1, 10, None, None)]);
+        service
+            .tow_truck_repository
+            .update_status(1, "off_duty")
+            .await
+            .unwrap();
+        let mut order_events = service.events.subscribe();
+        let mut tow_truck_events = service.tow_truck_events.subscribe();
+
+        let err = service
+            .create_dispatcher_order(1, 1, 1, Utc::now())
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.code(), "conflict");
+        assert!(order_events.try_recv().is_err());
+        assert!(tow_truck_events.try_recv().is_err());
+    }
+
+    #[actix_rt::test]
+    async fn subscribe_order_events_scopes_by_role() {
+        let service = service(vec![]);
+
+        let (scope, _) = service.subscribe_order_events(10).await.unwrap();
+        assert_eq!(scope, OrderEventScope::Client(10));
+        let (scope, _) = service.subscribe_order_events(20).await.unwrap();
+        assert_eq!(scope, OrderEventScope::Area(1));
+        let err = service.subscribe_order_events(30).await.unwrap_err();
+        assert_eq!(err.code(), "forbidden");
+        let err = service.subscribe_order_events(99).await.unwrap_err();
+        assert_eq!(err.code(), "unauthorized");
+    }
+
+    #[actix_rt::test]
+    async fn subscribe_order_events_gives_admins_every_area() {
+        let service = service(vec![order(1, 10, None, None)]);
+        service
+            .auth_repository
+            .update_role(30, ADMIN_ROLE)
+            .await
+            .unwrap();
+
+        let (scope, mut events) = service.subscribe_order_events(30).await.unwrap();
+        assert_eq!(scope, OrderEventScope::All);
+
+        service.update_order_status(1, "cancelled").await.unwrap();
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.kind, OrderEventKind::Cancelled);
+        assert!(scope.includes(&event));
     }
 }
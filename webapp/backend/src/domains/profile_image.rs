@@ -0,0 +1,428 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::io::{Limits, Reader as ImageReader};
+use image::{DynamicImage, ImageOutputFormat, Rgb, RgbImage};
+use log::error;
+use sha2::{Digest, Sha256};
+
+use crate::errors::AppError;
+
+pub const PROFILE_IMAGE_DIR: &str = "images/user_profile";
+pub const PROFILE_IMAGE_CACHE_DIR: &str = "images/cache/user_profile";
+
+// クライアントから任意のサイズを指定されて高コストなリサイズを強いられないよう、許可するサイズを限定する
+pub const ALLOWED_PROFILE_IMAGE_SIZES: [u32; 7] = [32, 64, 100, 128, 200, 256, 500];
+
+pub const MAX_UPLOAD_BYTES: usize = 5 * 1024 * 1024;
+const MIN_UPLOAD_DIMENSION: u32 = 16;
+const MAX_UPLOAD_DIMENSION: u32 = 4096;
+
+pub const DEFAULT_QUALITY: u8 = 80;
+
+const CONTENT_HASH_LEN: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileImageFormat {
+    Png,
+    Jpeg,
+    WebP,
+}
+
+impl ProfileImageFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Some(ProfileImageFormat::Png),
+            "jpeg" | "jpg" => Some(ProfileImageFormat::Jpeg),
+            "webp" => Some(ProfileImageFormat::WebP),
+            _ => None,
+        }
+    }
+
+    pub fn from_mime(essence: &str) -> Option<Self> {
+        match essence {
+            "image/png" => Some(ProfileImageFormat::Png),
+            "image/jpeg" => Some(ProfileImageFormat::Jpeg),
+            "image/webp" => Some(ProfileImageFormat::WebP),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ProfileImageFormat::Png => "image/png",
+            ProfileImageFormat::Jpeg => "image/jpeg",
+            ProfileImageFormat::WebP => "image/webp",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ProfileImageFormat::Png => "png",
+            ProfileImageFormat::Jpeg => "jpg",
+            ProfileImageFormat::WebP => "webp",
+        }
+    }
+
+    fn is_lossy(&self) -> bool {
+        !matches!(self, ProfileImageFormat::Png)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeMode {
+    // 縦横比を保ったまま指定サイズに収める（片方の辺が短くなる）
+    Fit,
+    // 縦横比を保ったまま指定サイズを覆うように拡縮し、はみ出た部分を中央基準で切り取る
+    Fill,
+    // 縦横比を無視して指定サイズに引き伸ばす
+    Exact,
+}
+
+impl ResizeMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "fit" => Some(ResizeMode::Fit),
+            "fill" => Some(ResizeMode::Fill),
+            "exact" => Some(ResizeMode::Exact),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            ResizeMode::Fit => "fit",
+            ResizeMode::Fill => "fill",
+            ResizeMode::Exact => "exact",
+        }
+    }
+}
+
+// クライアントから指定された変換内容（検証前）
+#[derive(Debug, Clone)]
+pub struct ProfileImageOptions {
+    pub width: i32,
+    pub height: i32,
+    pub mode: ResizeMode,
+    pub circle: bool,
+    pub format: ProfileImageFormat,
+    pub quality: Option<i32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProfileImageVariant {
+    pub image_name: String,
+    pub width: u32,
+    pub height: u32,
+    pub mode: ResizeMode,
+    // ドライバーのアバター表示用に、円形に切り抜いて外側を透過させる
+    pub circle: bool,
+    pub format: ProfileImageFormat,
+    pub quality: u8,
+}
+
+impl ProfileImageVariant {
+    pub fn cache_key(&self) -> String {
+        format!(
+            "{}@{}x{}-{}{}q{}.{}",
+            self.image_name,
+            self.width,
+            self.height,
+            self.mode.name(),
+            if self.circle { "-circle" } else { "" },
+            self.quality,
+            self.format.extension()
+        )
+    }
+
+    // 同じ画像・同じ変換内容であれば常に同じバイト列になるため、強い ETag として使える
+    pub fn etag(&self) -> String {
+        hex_digest(self.cache_key().as_bytes())
+    }
+
+    // アップロード時に内容のハッシュから命名された画像かどうか
+    pub fn is_content_hashed(&self) -> bool {
+        match self.image_name.strip_suffix(".png") {
+            Some(stem) => {
+                stem.len() == CONTENT_HASH_LEN
+                    && stem.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+            }
+            None => false,
+        }
+    }
+}
+
+fn hex_digest(bytes: &[u8]) -> String {
+    let digest = Sha256::digest(bytes);
+    digest[..CONTENT_HASH_LEN / 2]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub fn validate_size(width: i32, height: i32) -> Result<(u32, u32), AppError> {
+    let width = u32::try_from(width).map_err(|_| AppError::BadRequest)?;
+    let height = u32::try_from(height).map_err(|_| AppError::BadRequest)?;
+
+    if ALLOWED_PROFILE_IMAGE_SIZES.contains(&width) && ALLOWED_PROFILE_IMAGE_SIZES.contains(&height)
+    {
+        Ok((width, height))
+    } else {
+        Err(AppError::BadRequest)
+    }
+}
+
+// キャッシュのバリエーションが増えすぎないよう、品質は 10 刻みに丸める（可逆の PNG では無視する）
+pub fn normalize_quality(format: ProfileImageFormat, quality: Option<i32>) -> Result<u8, AppError> {
+    if !format.is_lossy() {
+        return Ok(0);
+    }
+
+    match quality {
+        Some(quality) if (1..=100).contains(&quality) => {
+            Ok((((quality + 5) / 10) * 10).clamp(10, 100) as u8)
+        }
+        Some(_) => Err(AppError::BadRequest),
+        None => Ok(DEFAULT_QUALITY),
+    }
+}
+
+pub fn render(path: &Path, variant: &ProfileImageVariant) -> Result<Vec<u8>, AppError> {
+    // 指定されたパスから画像を読み込む
+    let img = ImageReader::open(path)
+        .map_err(|e| {
+            error!("画像の読み込みに失敗しました: {:?}", e);
+            AppError::InternalServerError
+        })?
+        .decode()
+        .map_err(|e| {
+            error!("画像のデコードに失敗しました: {:?}", e);
+            AppError::InternalServerError
+        })?;
+
+    let transformed = transform(&img, variant);
+
+    encode(&transformed, variant.format, variant.quality).map_err(|e| {
+        error!("画像の書き込みに失敗しました: {:?}", e);
+        AppError::InternalServerError
+    })
+}
+
+fn transform(img: &DynamicImage, variant: &ProfileImageVariant) -> DynamicImage {
+    let (width, height) = (variant.width, variant.height);
+    let resized = match variant.mode {
+        ResizeMode::Fit => img.resize(width, height, FilterType::Lanczos3),
+        ResizeMode::Fill => img.resize_to_fill(width, height, FilterType::Lanczos3),
+        ResizeMode::Exact => img.resize_exact(width, height, FilterType::Lanczos3),
+    };
+
+    if variant.circle {
+        crop_circle(&resized)
+    } else {
+        resized
+    }
+}
+
+// 画像に内接する円の外側を透明にする
+fn crop_circle(img: &DynamicImage) -> DynamicImage {
+    let mut rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let center_x = width as f32 / 2.0;
+    let center_y = height as f32 / 2.0;
+    let radius = center_x.min(center_y);
+
+    for (x, y, pixel) in rgba.enumerate_pixels_mut() {
+        let dx = x as f32 + 0.5 - center_x;
+        let dy = y as f32 + 0.5 - center_y;
+        // 縁のジャギーを抑えるため、境界の 1px はアルファを線形に落とす
+        let coverage = (radius - (dx * dx + dy * dy).sqrt() + 0.5).clamp(0.0, 1.0);
+        pixel[3] = (pixel[3] as f32 * coverage).round() as u8;
+    }
+
+    DynamicImage::ImageRgba8(rgba)
+}
+
+fn encode(
+    img: &DynamicImage,
+    format: ProfileImageFormat,
+    quality: u8,
+) -> Result<Vec<u8>, image::ImageError> {
+    let mut buffer = Vec::new();
+    match format {
+        ProfileImageFormat::Png => {
+            img.write_to(&mut Cursor::new(&mut buffer), ImageOutputFormat::Png)?;
+        }
+        ProfileImageFormat::Jpeg => {
+            // JPEG はアルファチャンネルを持てないため、白背景に合成して RGB に変換する
+            let rgb = DynamicImage::ImageRgb8(flatten_on_white(img));
+            rgb.write_with_encoder(JpegEncoder::new_with_quality(&mut buffer, quality))?;
+        }
+        ProfileImageFormat::WebP => {
+            // image クレートの WebP エンコーダは可逆圧縮のみなので libwebp を直接使う
+            let rgba = img.to_rgba8();
+            let encoded = webp::Encoder::from_rgba(rgba.as_raw(), rgba.width(), rgba.height())
+                .encode(quality as f32);
+            buffer.extend_from_slice(&encoded);
+        }
+    }
+    Ok(buffer)
+}
+
+fn flatten_on_white(img: &DynamicImage) -> RgbImage {
+    let rgba = img.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let alpha = a as u16;
+        let blend = |c: u8| ((c as u16 * alpha + 255 * (255 - alpha)) / 255) as u8;
+        Rgb([blend(r), blend(g), blend(b)])
+    })
+}
+
+pub struct NormalizedImage {
+    pub file_name: String,
+    pub bytes: Vec<u8>,
+}
+
+// アップロードされた画像を検証し、メタデータを落とすために PNG へ再エンコードする
+pub fn normalize_upload(uploaded: &[u8]) -> Result<NormalizedImage, AppError> {
+    let mut reader = ImageReader::new(Cursor::new(uploaded))
+        .with_guessed_format()
+        .map_err(|_| AppError::BadRequest)?;
+    if reader.format().is_none() {
+        return Err(AppError::BadRequest);
+    }
+
+    // 巨大な画像を展開してメモリを使い切らないよう、デコード前に寸法を制限する
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_UPLOAD_DIMENSION);
+    limits.max_image_height = Some(MAX_UPLOAD_DIMENSION);
+    reader.limits(limits);
+
+    let img = reader.decode().map_err(|_| AppError::BadRequest)?;
+    if img.width() < MIN_UPLOAD_DIMENSION || img.height() < MIN_UPLOAD_DIMENSION {
+        return Err(AppError::BadRequest);
+    }
+
+    let mut bytes = Vec::new();
+    img.write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png)
+        .map_err(|e| {
+            error!("画像の書き込みに失敗しました: {:?}", e);
+            AppError::InternalServerError
+        })?;
+
+    // 内容のハッシュをファイル名にすることで、同名ファイルの上書きやキャッシュの不整合を防ぐ
+    Ok(NormalizedImage {
+        file_name: format!("{}.png", hex_digest(&bytes)),
+        bytes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const GREEN: Rgba<u8> = Rgba([0, 255, 0, 255]);
+    const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+
+    fn variant(width: u32, height: u32, mode: ResizeMode, circle: bool) -> ProfileImageVariant {
+        ProfileImageVariant {
+            image_name: "test.png".to_string(),
+            width,
+            height,
+            mode,
+            circle,
+            format: ProfileImageFormat::Png,
+            quality: 0,
+        }
+    }
+
+    // 左から赤・緑・青の縦縞（各 100px 幅）の 300x100 の画像
+    fn striped_image() -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(300, 100, |x, _| match x / 100 {
+            0 => RED,
+            1 => GREEN,
+            _ => BLUE,
+        }))
+    }
+
+    fn assert_close(actual: Rgba<u8>, expected: Rgba<u8>) {
+        for channel in 0..4 {
+            let diff = (actual[channel] as i32 - expected[channel] as i32).abs();
+            assert!(diff <= 8, "expected {:?}, got {:?}", expected, actual);
+        }
+    }
+
+    #[test]
+    fn exact_stretches_to_requested_size() {
+        let result = transform(
+            &striped_image(),
+            &variant(100, 100, ResizeMode::Exact, false),
+        );
+
+        assert_eq!((result.width(), result.height()), (100, 100));
+        // 横方向に潰されるので左端は赤のまま残る
+        assert_close(result.to_rgba8().get_pixel(5, 50).to_owned(), RED);
+    }
+
+    #[test]
+    fn fit_preserves_aspect_ratio_within_bounds() {
+        let result = transform(&striped_image(), &variant(150, 150, ResizeMode::Fit, false));
+
+        assert_eq!((result.width(), result.height()), (150, 50));
+    }
+
+    #[test]
+    fn fill_crops_around_center() {
+        let result = transform(
+            &striped_image(),
+            &variant(100, 100, ResizeMode::Fill, false),
+        );
+        let rgba = result.to_rgba8();
+
+        assert_eq!((result.width(), result.height()), (100, 100));
+        assert_close(rgba.get_pixel(50, 50).to_owned(), GREEN);
+        assert_close(rgba.get_pixel(10, 10).to_owned(), GREEN);
+        assert_close(rgba.get_pixel(90, 90).to_owned(), GREEN);
+    }
+
+    #[test]
+    fn circle_makes_corners_transparent() {
+        let result = transform(&striped_image(), &variant(100, 100, ResizeMode::Fill, true));
+        let rgba = result.to_rgba8();
+
+        assert_eq!(rgba.get_pixel(0, 0)[3], 0);
+        assert_eq!(rgba.get_pixel(99, 0)[3], 0);
+        assert_eq!(rgba.get_pixel(0, 99)[3], 0);
+        assert_eq!(rgba.get_pixel(99, 99)[3], 0);
+        assert_close(rgba.get_pixel(50, 50).to_owned(), GREEN);
+    }
+
+    #[test]
+    fn jpeg_output_flattens_transparency_on_white() {
+        let circle = transform(&striped_image(), &variant(100, 100, ResizeMode::Fill, true));
+        let flattened = flatten_on_white(&circle);
+
+        assert_eq!(flattened.get_pixel(0, 0), &Rgb([255, 255, 255]));
+    }
+
+    #[test]
+    fn cache_key_distinguishes_modes_and_circle() {
+        let keys = [
+            variant(100, 100, ResizeMode::Fit, false).cache_key(),
+            variant(100, 100, ResizeMode::Fill, false).cache_key(),
+            variant(100, 100, ResizeMode::Exact, false).cache_key(),
+            variant(100, 100, ResizeMode::Fill, true).cache_key(),
+        ];
+
+        for (i, a) in keys.iter().enumerate() {
+            for b in keys.iter().skip(i + 1) {
+                assert_ne!(a, b);
+            }
+        }
+    }
+}
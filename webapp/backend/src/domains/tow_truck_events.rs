@@ -0,0 +1,120 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use super::dto::tow_truck::TowTruckDto;
+use crate::models::tow_truck::TowTruck;
+
+// 購読者の受信が追いつかない場合に保持しておくイベント数
+const TOW_TRUCK_EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TowTruckEventKind {
+    LocationUpdated,
+    StatusChanged,
+}
+
+// 変更後のレッカー車の状態をそのまま配信する
+#[derive(Clone, Serialize)]
+pub struct TowTruckEvent {
+    pub kind: TowTruckEventKind,
+    pub tow_truck: TowTruckDto,
+    pub occurred_at: DateTime<Utc>,
+}
+
+impl TowTruckEvent {
+    pub fn new(kind: TowTruckEventKind, tow_truck: TowTruck) -> Self {
+        TowTruckEvent {
+            kind,
+            tow_truck: TowTruckDto::from_entity(tow_truck),
+            occurred_at: Utc::now(),
+        }
+    }
+
+    // エリアごとの購読者に配信するかどうか
+    pub fn is_in_area(&self, area_id: i32) -> bool {
+        self.tow_truck.area_id == area_id
+    }
+}
+
+// TowTruckService の更新処理から発行されたイベントを購読者に配信する
+#[derive(Debug)]
+pub struct TowTruckEventBus {
+    sender: broadcast::Sender<TowTruckEvent>,
+}
+
+impl TowTruckEventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(TOW_TRUCK_EVENT_CHANNEL_CAPACITY);
+        TowTruckEventBus { sender }
+    }
+
+    pub fn publish(&self, event: TowTruckEvent) {
+        // 購読者がいない場合の送信エラーは無視してよい
+        let _ = self.sender.send(event);
+    }
+
+    // 購読者がいなければ、イベントを組み立てるための読み込みを省ける
+    pub fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<TowTruckEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for TowTruckEventBus {
+    fn default() -> Self {
+        TowTruckEventBus::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tow_truck(id: i32, area_id: i32) -> TowTruck {
+        TowTruck {
+            id,
+            driver_id: 30,
+            driver_username: Some("driver".to_string()),
+            status: "available".to_string(),
+            area_id,
+            node_id: 1,
+        }
+    }
+
+    #[test]
+    fn is_in_area_matches_tow_truck_area() {
+        let event = TowTruckEvent::new(TowTruckEventKind::LocationUpdated, tow_truck(1, 2));
+
+        assert!(event.is_in_area(2));
+        assert!(!event.is_in_area(1));
+    }
+
+    #[test]
+    fn event_serializes_kind_and_tow_truck() {
+        let event = TowTruckEvent::new(TowTruckEventKind::StatusChanged, tow_truck(1, 2));
+
+        let json = serde_json::to_value(&event).unwrap();
+
+        assert_eq!(json["kind"], "status_changed");
+        assert_eq!(json["tow_truck"]["id"], 1);
+        assert_eq!(json["tow_truck"]["area_id"], 2);
+        assert_eq!(json["tow_truck"]["status"], "available");
+    }
+
+    #[test]
+    fn has_subscribers_follows_receivers() {
+        let bus = TowTruckEventBus::new();
+        assert!(!bus.has_subscribers());
+
+        let receiver = bus.subscribe();
+        assert!(bus.has_subscribers());
+
+        drop(receiver);
+        assert!(!bus.has_subscribers());
+    }
+}
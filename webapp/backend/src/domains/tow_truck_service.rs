@@ -1,9 +1,21 @@
-use super::dto::tow_truck::TowTruckDto;
-use super::map_service::MapRepository;
+use std::sync::Arc;
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use log::warn;
+use tokio::sync::broadcast;
+use tracing::instrument;
+
+use super::dto::tow_truck::{NearestTowTruckDto, OnShiftDriverDto, TowTruckDto};
+use super::eta_service::EtaService;
+use super::map_service::{load_area_graph, MapRepository};
 use super::order_service::OrderRepository;
+use super::tow_truck_events::{TowTruckEvent, TowTruckEventBus, TowTruckEventKind};
 use crate::errors::AppError;
+use crate::infrastructure::graph_cache::GraphCache;
+use crate::infrastructure::metrics::metrics;
 use crate::models::graph::Graph;
-use crate::models::tow_truck::TowTruck;
+use crate::models::tow_truck::{DriverShift, OnShiftDriver, TowTruck};
 
 pub trait TowTruckRepository {
     async fn get_paginated_tow_trucks(
@@ -16,6 +28,35 @@ pub trait TowTruckRepository {
     async fn update_location(&self, truck_id: i32, node_id: i32) -> Result<(), AppError>;
     async fn update_status(&self, truck_id: i32, status: &str) -> Result<(), AppError>;
     async fn find_tow_truck_by_id(&self, id: i32) -> Result<Option<TowTruck>, AppError>;
+    async fn find_tow_truck_by_driver_id(
+        &self,
+        driver_id: i32,
+    ) -> Result<Option<TowTruck>, AppError>;
+    async fn start_shift(
+        &self,
+        tow_truck_id: i32,
+        driver_id: i32,
+        area_id: i32,
+        started_at: DateTime<Utc>,
+    ) -> Result<i32, AppError>;
+    async fn end_shift(
+        &self,
+        shift_id: i32,
+        tow_truck_id: i32,
+        ended_at: DateTime<Utc>,
+    ) -> Result<bool, AppError>;
+    async fn find_open_shift_by_driver_id(
+        &self,
+        driver_id: i32,
+    ) -> Result<Option<DriverShift>, AppError>;
+    async fn find_shift_by_id(&self, id: i32) -> Result<Option<DriverShift>, AppError>;
+    async fn get_shifts_by_driver_id(
+        &self,
+        driver_id: i32,
+        page: i32,
+        page_size: i32,
+    ) -> Result<Vec<DriverShift>, AppError>;
+    async fn get_on_shift_drivers(&self, area_id: i32) -> Result<Vec<OnShiftDriver>, AppError>;
 }
 
 #[derive(Debug)]
@@ -27,6 +68,9 @@ pub struct TowTruckService<
     tow_truck_repository: T,
     order_repository: U,
     map_repository: V,
+    graph_cache: Arc<GraphCache>,
+    events: Arc<TowTruckEventBus>,
+    eta_service: Arc<EtaService>,
 }
 
 impl<
@@ -35,19 +79,31 @@ impl<
         V: MapRepository + std::fmt::Debug,
     > TowTruckService<T, U, V>
 {
-    pub fn new(tow_truck_repository: T, order_repository: U, map_repository: V) -> Self {
+    pub fn new(
+        tow_truck_repository: T,
+        order_repository: U,
+        map_repository: V,
+        graph_cache: Arc<GraphCache>,
+        events: Arc<TowTruckEventBus>,
+        eta_service: Arc<EtaService>,
+    ) -> Self {
         TowTruckService {
             tow_truck_repository,
             order_repository,
             map_repository,
+            graph_cache,
+            events,
+            eta_service,
         }
     }
 
+    #[instrument(skip(self))]
     pub async fn get_tow_truck_by_id(&self, id: i32) -> Result<Option<TowTruckDto>, AppError> {
         let tow_truck = self.tow_truck_repository.find_tow_truck_by_id(id).await?;
         Ok(tow_truck.map(TowTruckDto::from_entity))
     }
 
+    #[instrument(skip(self))]
     pub async fn get_all_tow_trucks(
         &self,
         page: i32,
@@ -67,18 +123,58 @@ impl<
         Ok(tow_truck_dtos)
     }
 
+    #[instrument(skip(self))]
     pub async fn update_location(&self, truck_id: i32, node_id: i32) -> Result<(), AppError> {
         self.tow_truck_repository
             .update_location(truck_id, node_id)
             .await?;
+        publish_tow_truck_event(
+            &self.tow_truck_repository,
+            &self.events,
+            TowTruckEventKind::LocationUpdated,
+            truck_id,
+        )
+        .await;
 
         Ok(())
     }
 
+    // エリア内で勤務中のドライバーを勤務開始が早い順に返す
+    #[instrument(skip(self))]
+    pub async fn get_on_shift_drivers(
+        &self,
+        area_id: i32,
+    ) -> Result<Vec<OnShiftDriverDto>, AppError> {
+        let now = Utc::now();
+        let drivers = self
+            .tow_truck_repository
+            .get_on_shift_drivers(area_id)
+            .await?;
+
+        Ok(drivers
+            .into_iter()
+            .map(|driver| OnShiftDriverDto::from_entity(driver, now))
+            .collect())
+    }
+
+    // エリア内のレッカー車の現在の状態と、以降の変更を受け取るレシーバーを返す
+    // 取りこぼしがないよう、購読を開始してから現在の状態を読み込む
+    #[instrument(skip(self))]
+    pub async fn subscribe_area(
+        &self,
+        area_id: i32,
+    ) -> Result<(Vec<TowTruckDto>, broadcast::Receiver<TowTruckEvent>), AppError> {
+        let receiver = self.events.subscribe();
+        let snapshot = self.get_all_tow_trucks(0, -1, None, Some(area_id)).await?;
+
+        Ok((snapshot, receiver))
+    }
+
+    #[instrument(skip(self))]
     pub async fn get_nearest_available_tow_trucks(
         &self,
         order_id: i32,
-    ) -> Result<Option<TowTruckDto>, AppError> {
+    ) -> Result<Option<NearestTowTruckDto>, AppError> {
         let order = self.order_repository.find_order_by_id(order_id).await?;
         let area_id = self
             .map_repository
@@ -89,16 +185,7 @@ impl<
             .get_paginated_tow_trucks(0, -1, Some("available".to_string()), Some(area_id))
             .await?;
 
-        let nodes = self.map_repository.get_all_nodes(Some(area_id)).await?;
-        let edges = self.map_repository.get_all_edges(Some(area_id)).await?;
-
-        let mut graph = Graph::new();
-        for node in nodes {
-            graph.add_node(node);
-        }
-        for edge in edges {
-            graph.add_edge(edge);
-        }
+        let graph = load_area_graph(&self.map_repository, &self.graph_cache, area_id).await?;
 
         let sorted_tow_trucks_by_distance = {
             let mut tow_trucks_with_distance: Vec<_> = tow_trucks
@@ -113,20 +200,128 @@ impl<
             tow_trucks_with_distance
         };
 
-        if sorted_tow_trucks_by_distance.is_empty() || sorted_tow_trucks_by_distance[0].0 > 10000000
-        {
-            return Ok(None);
-        }
+        let (distance, tow_truck) = match sorted_tow_trucks_by_distance.into_iter().next() {
+            Some((distance, tow_truck)) if distance <= 10000000 => (distance, tow_truck),
+            _ => return Ok(None),
+        };
 
-        let sorted_tow_truck_dtos: Vec<TowTruckDto> = sorted_tow_trucks_by_distance
-            .into_iter()
-            .map(|(_, truck)| TowTruckDto::from_entity(truck))
-            .collect();
+        Ok(Some(NearestTowTruckDto {
+            tow_truck: TowTruckDto::from_entity(tow_truck),
+            eta: self.eta_service.estimate(area_id, distance, Utc::now()),
+        }))
+    }
+}
+
+// 更新後のレッカー車を読み直して購読者に配信する
+// 更新前に読み込んだ値から組み立てられる場合は TowTruckEvent::new を直接使う
+// 配信に失敗しても更新処理自体は成功として扱う
+pub async fn publish_tow_truck_event<T: TowTruckRepository>(
+    repository: &T,
+    events: &TowTruckEventBus,
+    kind: TowTruckEventKind,
+    truck_id: i32,
+) {
+    if !events.has_subscribers() {
+        return;
+    }
 
-        Ok(sorted_tow_truck_dtos.first().cloned())
+    match repository.find_tow_truck_by_id(truck_id).await {
+        Ok(Some(tow_truck)) => events.publish(TowTruckEvent::new(kind, tow_truck)),
+        Ok(None) => warn!(
+            "tow truck {} was not found while publishing {:?} event",
+            truck_id, kind
+        ),
+        Err(e) => warn!(
+            "failed to load tow truck {} for {:?} event: {:?}",
+            truck_id, kind, e
+        ),
     }
 }
 
-fn calculate_distance(graph: &Graph, node_id_1: i32, node_id_2: i32) -> i32 {
-    graph.shortest_path(node_id_1, node_id_2)
+// 到達できない場合は i32::MAX を返す
+pub fn calculate_distance(graph: &Graph, node_id_1: i32, node_id_2: i32) -> i32 {
+    let started_at = Instant::now();
+    let (distance, visited_nodes) = graph.shortest_path_with_stats(node_id_1, node_id_2);
+    metrics().observe_shortest_path(started_at.elapsed(), visited_nodes);
+    distance
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::config::EtaConfig;
+    use crate::models::graph::Edge;
+    use crate::repositories::in_memory::{
+        InMemoryMapRepository, InMemoryOrderRepository, InMemoryTowTruckRepository,
+    };
+
+    type TestTowTruckService =
+        TowTruckService<InMemoryTowTruckRepository, InMemoryOrderRepository, InMemoryMapRepository>;
+
+    fn tow_truck(id: i32, area_id: i32, node_id: i32) -> TowTruck {
+        TowTruck {
+            id,
+            driver_id: 30 + id,
+            driver_username: None,
+            status: "available".to_string(),
+            area_id,
+            node_id,
+        }
+    }
+
+    fn service(tow_trucks: Vec<TowTruck>) -> TestTowTruckService {
+        let graph_cache = Arc::new(GraphCache::new());
+        TowTruckService::new(
+            InMemoryTowTruckRepository::new(tow_trucks),
+            InMemoryOrderRepository::new(vec![]),
+            InMemoryMapRepository::new(
+                HashMap::from([(1, 1), (2, 1), (3, 2)]),
+                vec![Edge {
+                    node_a_id: 1,
+                    node_b_id: 2,
+                    weight: 5,
+                }],
+            ),
+            graph_cache.clone(),
+            Arc::new(TowTruckEventBus::new()),
+            Arc::new(EtaService::new(
+                &EtaConfig {
+                    weight_unit_secs: 60.0,
+                    area_speed_factors: HashMap::new(),
+                },
+                graph_cache,
+            )),
+        )
+    }
+
+    #[actix_rt::test]
+    async fn subscribe_area_returns_snapshot_of_the_area() {
+        let service = service(vec![tow_truck(1, 1, 1), tow_truck(2, 2, 3)]);
+
+        let (snapshot, _) = service.subscribe_area(1).await.unwrap();
+
+        let ids: Vec<i32> = snapshot.iter().map(|tow_truck| tow_truck.id).collect();
+        assert_eq!(ids, vec![1]);
+    }
+
+    #[actix_rt::test]
+    async fn update_location_publishes_moved_tow_truck() {
+        let service = service(vec![tow_truck(1, 1, 1), tow_truck(2, 2, 3)]);
+        let (_, mut receiver) = service.subscribe_area(1).await.unwrap();
+
+        service.update_location(1, 2).await.unwrap();
+        service.update_location(2, 3).await.unwrap();
+
+        let event = receiver.try_recv().unwrap();
+        assert_eq!(event.kind, TowTruckEventKind::LocationUpdated);
+        assert_eq!(event.tow_truck.id, 1);
+        assert_eq!(event.tow_truck.node_id, 2);
+        assert!(event.is_in_area(1));
+        // 他のエリアのイベントも届くため、購読側で絞り込む
+        let event = receiver.try_recv().unwrap();
+        assert_eq!(event.tow_truck.id, 2);
+        assert!(!event.is_in_area(1));
+    }
 }
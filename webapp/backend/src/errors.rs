@@ -1,44 +1,383 @@
-use actix_web::{HttpResponse, ResponseError};
+use actix_web::{
+    http::{header::RETRY_AFTER, StatusCode},
+    HttpResponse, ResponseError,
+};
+use log::error;
 use serde::Serialize;
+use sqlx::mysql::MySqlDatabaseError;
 use thiserror::Error;
+use validator::{ValidationErrors, ValidationErrorsKind};
+
+use crate::middlewares::request_id_middleware::current_request_id;
+
+// MySQL のエラー番号
+const ER_DUP_ENTRY: u16 = 1062;
+const ER_ROW_IS_REFERENCED_2: u16 = 1451;
+const ER_NO_REFERENCED_ROW_2: u16 = 1452;
 
 #[derive(Debug, Error)]
 pub enum AppError {
     #[error("Bad Request")]
     BadRequest,
+    #[error("Bad Request")]
+    ValidationError(#[from] ValidationErrors),
     #[error("Unauthorized")]
     Unauthorized,
+    #[error("Forbidden")]
+    Forbidden,
     #[error("Not Found")]
     NotFound,
+    #[error("Not Acceptable")]
+    NotAcceptable,
     #[error("Conflict")]
     Conflict,
+    #[error("Payload Too Large")]
+    PayloadTooLarge,
+    #[error("Too Many Requests")]
+    TooManyRequests { retry_after_secs: u64 },
     #[error("Internal Server Error")]
     InternalServerError,
     #[error(transparent)]
     SqlxError(#[from] sqlx::Error),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SqlxErrorKind {
+    RowNotFound,
+    UniqueViolation,
+    ForeignKeyViolation,
+    Other,
+}
+
+fn classify_sqlx_error(err: &sqlx::Error) -> SqlxErrorKind {
+    match err {
+        sqlx::Error::RowNotFound => SqlxErrorKind::RowNotFound,
+        sqlx::Error::Database(db_err) => match db_err.try_downcast_ref::<MySqlDatabaseError>() {
+            Some(mysql_err) => match mysql_err.number() {
+                ER_DUP_ENTRY => SqlxErrorKind::UniqueViolation,
+                ER_ROW_IS_REFERENCED_2 | ER_NO_REFERENCED_ROW_2 => {
+                    SqlxErrorKind::ForeignKeyViolation
+                }
+                _ => SqlxErrorKind::Other,
+            },
+            None => SqlxErrorKind::Other,
+        },
+        _ => SqlxErrorKind::Other,
+    }
+}
+
+impl AppError {
+    // クライアントが分岐に使う安定したエラーコード
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::BadRequest => "bad_request",
+            AppError::ValidationError(_) => "validation_failed",
+            AppError::Unauthorized => "unauthorized",
+            AppError::Forbidden => "forbidden",
+            AppError::NotFound => "not_found",
+            AppError::NotAcceptable => "not_acceptable",
+            AppError::Conflict => "conflict",
+            AppError::PayloadTooLarge => "payload_too_large",
+            AppError::TooManyRequests { .. } => "too_many_requests",
+            AppError::InternalServerError => "internal_error",
+            AppError::SqlxError(err) => match classify_sqlx_error(err) {
+                SqlxErrorKind::RowNotFound => "not_found",
+                SqlxErrorKind::UniqueViolation => "conflict",
+                SqlxErrorKind::ForeignKeyViolation => "invalid_reference",
+                SqlxErrorKind::Other => "database_error",
+            },
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            AppError::ValidationError(_) => "Request validation failed".to_string(),
+            // DB のエラーメッセージはそのまま返さない
+            AppError::SqlxError(err) => match classify_sqlx_error(err) {
+                SqlxErrorKind::RowNotFound => "Not Found".to_string(),
+                SqlxErrorKind::UniqueViolation => "Conflict".to_string(),
+                SqlxErrorKind::ForeignKeyViolation => {
+                    "Referenced resource does not exist".to_string()
+                }
+                SqlxErrorKind::Other => "Internal Server Error".to_string(),
+            },
+            _ => self.to_string(),
+        }
+    }
+}
+
 #[derive(Serialize)]
 struct ErrorResponse {
+    code: &'static str,
     message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    details: Vec<FieldErrorResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct FieldErrorResponse {
+    field: String,
+    code: String,
+    message: String,
+}
+
+fn field_errors(errors: &ValidationErrors) -> Vec<FieldErrorResponse> {
+    let mut field_errors = Vec::new();
+    collect_field_errors("", errors, &mut field_errors);
+    field_errors
+}
+
+// ValidationError の Display は入力値 (params の value) を含むため、
+// パスワードなどの送信内容を返さないよう code ごとの固定の文言を使う
+fn default_validation_message(code: &str) -> &'static str {
+    match code {
+        "length" => "長さが許容範囲外です",
+        "range" => "値が許容範囲外です",
+        "required" => "必須の項目です",
+        _ => "不正な値です",
+    }
+}
+
+// ネストした構造体や配列の要素のエラーも edges[3].weight のようなパスで列挙する
+fn collect_field_errors(
+    prefix: &str,
+    errors: &ValidationErrors,
+    field_errors: &mut Vec<FieldErrorResponse>,
+) {
+    let mut fields: Vec<_> = errors.errors().iter().collect();
+    fields.sort_by_key(|(field, _)| **field);
+
+    for (field, kind) in fields {
+        let path = if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{}.{}", prefix, field)
+        };
+
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                field_errors.extend(errors.iter().map(|error| FieldErrorResponse {
+                    field: path.clone(),
+                    code: error.code.to_string(),
+                    message: match &error.message {
+                        Some(message) => message.to_string(),
+                        None => default_validation_message(&error.code).to_string(),
+                    },
+                }))
+            }
+            ValidationErrorsKind::Struct(errors) => {
+                collect_field_errors(&path, errors, field_errors)
+            }
+            ValidationErrorsKind::List(items) => {
+                for (index, errors) in items {
+                    collect_field_errors(&format!("{}[{}]", path, index), errors, field_errors);
+                }
+            }
+        }
+    }
 }
 
 impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest | AppError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden => StatusCode::FORBIDDEN,
+            AppError::NotFound => StatusCode::NOT_FOUND,
+            AppError::NotAcceptable => StatusCode::NOT_ACCEPTABLE,
+            AppError::Conflict => StatusCode::CONFLICT,
+            AppError::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::SqlxError(err) => match classify_sqlx_error(err) {
+                SqlxErrorKind::RowNotFound => StatusCode::NOT_FOUND,
+                SqlxErrorKind::UniqueViolation => StatusCode::CONFLICT,
+                SqlxErrorKind::ForeignKeyViolation => StatusCode::BAD_REQUEST,
+                SqlxErrorKind::Other => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+        }
+    }
+
     fn error_response(&self) -> HttpResponse {
-        let error_message = self.to_string();
+        let request_id = current_request_id();
+        let status_code = self.status_code();
+
+        if status_code.is_server_error() {
+            error!(
+                "request_id={} {}",
+                request_id.as_deref().unwrap_or("-"),
+                self
+            );
+        }
+
         let error_response = ErrorResponse {
-            message: error_message,
+            code: self.code(),
+            message: self.message(),
+            details: match self {
+                AppError::ValidationError(errors) => field_errors(errors),
+                _ => Vec::new(),
+            },
+            request_id,
         };
 
-        match *self {
-            AppError::BadRequest => HttpResponse::BadRequest().json(error_response),
-            AppError::Unauthorized => HttpResponse::Unauthorized().json(error_response),
-            AppError::NotFound => HttpResponse::NotFound().json(error_response),
-            AppError::Conflict => HttpResponse::Conflict().json(error_response),
-            AppError::InternalServerError => {
-                HttpResponse::InternalServerError().json(error_response)
-            }
-            AppError::SqlxError(_) => HttpResponse::InternalServerError().json(error_response),
+        let mut response = HttpResponse::build(status_code);
+        if let AppError::TooManyRequests { retry_after_secs } = self {
+            response.insert_header((RETRY_AFTER, retry_after_secs.to_string()));
+        }
+        response.json(error_response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::body::to_bytes;
+    use serde_json::Value;
+    use validator::Validate;
+
+    use super::*;
+    use crate::domains::dto::auth::LoginRequestDto;
+    use crate::domains::dto::map::BulkUpdateEdgesRequestDto;
+
+    async fn body_of(err: AppError) -> (StatusCode, Value) {
+        let res = err.error_response();
+        let status = res.status();
+        let body = to_bytes(res.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[actix_rt::test]
+    async fn error_body_contains_code_and_message() {
+        let cases = [
+            (AppError::BadRequest, StatusCode::BAD_REQUEST, "bad_request"),
+            (
+                AppError::Unauthorized,
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+            ),
+            (AppError::Forbidden, StatusCode::FORBIDDEN, "forbidden"),
+            (AppError::NotFound, StatusCode::NOT_FOUND, "not_found"),
+            (AppError::Conflict, StatusCode::CONFLICT, "conflict"),
+            (
+                AppError::InternalServerError,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+            ),
+        ];
+
+        for (err, expected_status, expected_code) in cases {
+            let message = err.to_string();
+            let (status, body) = body_of(err).await;
+
+            assert_eq!(status, expected_status);
+            assert_eq!(body["code"], expected_code);
+            assert_eq!(body["message"], message);
+            assert!(body.get("details").is_none());
+            // リクエストのスコープ外では request_id を含めない
+            assert!(body.get("request_id").is_none());
         }
     }
+
+    #[actix_rt::test]
+    async fn row_not_found_maps_to_404() {
+        let (status, body) = body_of(AppError::SqlxError(sqlx::Error::RowNotFound)).await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "not_found");
+        assert_eq!(body["message"], "Not Found");
+    }
+
+    #[actix_rt::test]
+    async fn other_database_errors_hide_details() {
+        let err = AppError::SqlxError(sqlx::Error::Protocol("secret detail".to_string()));
+
+        let (status, body) = body_of(err).await;
+
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["code"], "database_error");
+        assert_eq!(body["message"], "Internal Server Error");
+    }
+
+    #[actix_rt::test]
+    async fn too_many_requests_sets_retry_after() {
+        let res = AppError::TooManyRequests {
+            retry_after_secs: 42,
+        }
+        .error_response();
+
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers().get(RETRY_AFTER).unwrap(), "42");
+    }
+
+    #[derive(Validate)]
+    struct Request {
+        #[validate(length(min = 1))]
+        name: String,
+        #[validate(range(min = 1))]
+        age: i32,
+    }
+
+    #[actix_rt::test]
+    async fn validation_errors_list_fields_in_order() {
+        let errors = Request {
+            name: String::new(),
+            age: 0,
+        }
+        .validate()
+        .unwrap_err();
+
+        let (status, body) = body_of(AppError::ValidationError(errors)).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "validation_failed");
+        let details = body["details"].as_array().unwrap();
+        assert_eq!(details.len(), 2);
+        assert_eq!(details[0]["field"], "age");
+        assert_eq!(details[0]["code"], "range");
+        assert_eq!(details[1]["field"], "name");
+        assert_eq!(details[1]["code"], "length");
+    }
+
+    #[actix_rt::test]
+    async fn validation_errors_include_nested_list_items() {
+        let request: BulkUpdateEdgesRequestDto = serde_json::from_value(serde_json::json!({
+            "edges": [
+                { "node_a_id": 1, "node_b_id": 2, "weight": 3 },
+                { "node_a_id": 1, "node_b_id": 2, "weight": 3 },
+                { "node_a_id": 1, "node_b_id": 2, "weight": 3 },
+                { "node_a_id": 0, "node_b_id": 2, "weight": -1 },
+            ]
+        }))
+        .unwrap();
+        let errors = request.validate().unwrap_err();
+
+        let (status, body) = body_of(AppError::ValidationError(errors)).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let fields: Vec<&str> = body["details"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|detail| detail["field"].as_str().unwrap())
+            .collect();
+        assert_eq!(fields, vec!["edges[3].node_a_id", "edges[3].weight"]);
+    }
+
+    #[actix_rt::test]
+    async fn validation_errors_do_not_echo_submitted_values() {
+        let password = format!("secret-{}", "x".repeat(128));
+        let errors = LoginRequestDto {
+            username: "client".to_string(),
+            password: password.clone(),
+        }
+        .validate()
+        .unwrap_err();
+
+        let (_, body) = body_of(AppError::ValidationError(errors)).await;
+
+        assert_eq!(body["details"][0]["field"], "password");
+        assert_eq!(body["details"][0]["message"], "長さが許容範囲外です");
+        assert!(!body.to_string().contains("secret-"));
+    }
 }
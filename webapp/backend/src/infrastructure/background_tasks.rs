@@ -0,0 +1,156 @@
+use std::future::Future;
+use std::time::Duration;
+
+use actix_web::rt;
+use log::{info, warn};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time;
+
+// アプリケーションが起動している間、定期的に実行するタスクを管理する
+pub struct BackgroundTasks {
+    shutdown: watch::Sender<bool>,
+    handles: Vec<(&'static str, JoinHandle<()>)>,
+}
+
+impl BackgroundTasks {
+    pub fn new() -> Self {
+        let (shutdown, _) = watch::channel(false);
+        BackgroundTasks {
+            shutdown,
+            handles: Vec::new(),
+        }
+    }
+
+    // 起動直後に 1 回実行し、以降は interval ごとに実行する
+    // 実行中のタスクは中断せず、完了してから停止要求を確認する
+    pub fn spawn_periodic<F, Fut>(&mut self, name: &'static str, interval: Duration, task: F)
+    where
+        F: Fn() -> Fut + 'static,
+        Fut: Future<Output = ()> + 'static,
+    {
+        let mut shutdown = self.shutdown.subscribe();
+
+        let handle = rt::spawn(async move {
+            let mut ticker = time::interval(interval);
+            ticker.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+
+            loop {
+                tokio::select! {
+                    _ = shutdown.changed() => break,
+                    _ = ticker.tick() => task().await,
+                }
+            }
+            info!("バックグラウンドタスク {} を停止しました", name);
+        });
+
+        self.handles.push((name, handle));
+    }
+
+    // すべてのタスクに停止を要求し、timeout までに終わらなかったものは中断する
+    pub async fn shutdown(self, timeout: Duration) {
+        let _ = self.shutdown.send(true);
+        let deadline = time::Instant::now() + timeout;
+
+        for (name, handle) in self.handles {
+            let abort_handle = handle.abort_handle();
+            if time::timeout_at(deadline, handle).await.is_err() {
+                warn!(
+                    "バックグラウンドタスク {} が {:?} 以内に停止しなかったため中断します",
+                    name, timeout
+                );
+                abort_handle.abort();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    use super::*;
+
+    // 負荷が高いとタスクの開始が遅れるため、固定時間待つ代わりに条件を満たすまで待つ
+    async fn wait_until(condition: impl Fn() -> bool) {
+        let deadline = time::Instant::now() + Duration::from_secs(5);
+        while !condition() {
+            assert!(time::Instant::now() < deadline, "condition was not met");
+            time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    #[actix_rt::test]
+    async fn runs_immediately_and_then_every_interval() {
+        let runs = Rc::new(RefCell::new(Vec::new()));
+        let mut tasks = BackgroundTasks::new();
+
+        let recorder = runs.clone();
+        let spawned_at = time::Instant::now();
+        tasks.spawn_periodic("counter", Duration::from_millis(200), move || {
+            let recorder = recorder.clone();
+            async move { recorder.borrow_mut().push(time::Instant::now()) }
+        });
+
+        wait_until(|| runs.borrow().len() >= 3).await;
+        tasks.shutdown(Duration::from_secs(1)).await;
+        let runs_at_shutdown = runs.borrow().clone();
+
+        assert!(runs_at_shutdown[0] - spawned_at < Duration::from_millis(150));
+        for pair in runs_at_shutdown.windows(2) {
+            assert!(pair[1] - pair[0] >= Duration::from_millis(190));
+        }
+
+        // 停止後は実行されない
+        time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(runs.borrow().len(), runs_at_shutdown.len());
+    }
+
+    #[actix_rt::test]
+    async fn shutdown_waits_for_running_task() {
+        let started = Rc::new(Cell::new(false));
+        let finished = Rc::new(Cell::new(false));
+        let mut tasks = BackgroundTasks::new();
+
+        let (started_flag, finished_flag) = (started.clone(), finished.clone());
+        tasks.spawn_periodic("slow", Duration::from_secs(60), move || {
+            let (started_flag, finished_flag) = (started_flag.clone(), finished_flag.clone());
+            async move {
+                started_flag.set(true);
+                time::sleep(Duration::from_millis(50)).await;
+                finished_flag.set(true);
+            }
+        });
+
+        wait_until(|| started.get()).await;
+        tasks.shutdown(Duration::from_secs(1)).await;
+
+        assert!(finished.get());
+    }
+
+    #[actix_rt::test]
+    async fn shutdown_aborts_task_exceeding_timeout() {
+        let started = Rc::new(Cell::new(false));
+        let finished = Rc::new(Cell::new(false));
+        let mut tasks = BackgroundTasks::new();
+
+        let (started_flag, finished_flag) = (started.clone(), finished.clone());
+        tasks.spawn_periodic("stuck", Duration::from_secs(60), move || {
+            let (started_flag, finished_flag) = (started_flag.clone(), finished_flag.clone());
+            async move {
+                started_flag.set(true);
+                time::sleep(Duration::from_secs(10)).await;
+                finished_flag.set(true);
+            }
+        });
+
+        wait_until(|| started.get()).await;
+        let started_at = time::Instant::now();
+        tasks.shutdown(Duration::from_millis(50)).await;
+
+        assert!(started_at.elapsed() < Duration::from_secs(1));
+        time::sleep(Duration::from_millis(50)).await;
+        assert!(!finished.get());
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::models::graph::Graph;
+
+// エリアごとに構築済みの道路グラフを保持し、最短経路探索のたびに DB から読み直さないようにする
+#[derive(Debug, Default)]
+struct Areas {
+    graphs: HashMap<i32, Arc<Graph>>,
+    // invalidate() のたびに増やす。構築中に無効化されたグラフをキャッシュに戻さないために使う
+    generations: HashMap<i32, u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct GraphCache {
+    areas: RwLock<Areas>,
+    // 起動時の全エリアの読み込みが完了したかどうか
+    warmed: AtomicBool,
+}
+
+impl GraphCache {
+    pub fn new() -> Self {
+        GraphCache::default()
+    }
+
+    pub fn get(&self, area_id: i32) -> Option<Arc<Graph>> {
+        self.areas.read().unwrap().graphs.get(&area_id).cloned()
+    }
+
+    // グラフの構築を始める前に取得し、insert() に渡す
+    pub fn generation(&self, area_id: i32) -> u64 {
+        let areas = self.areas.read().unwrap();
+        areas.generations.get(&area_id).copied().unwrap_or(0)
+    }
+
+    // generation の取得後に invalidate() された場合、グラフは古い可能性があるのでキャッシュしない
+    pub fn insert(&self, area_id: i32, generation: u64, graph: Graph) -> Arc<Graph> {
+        let graph = Arc::new(graph);
+        let mut areas = self.areas.write().unwrap();
+        if areas.generations.get(&area_id).copied().unwrap_or(0) == generation {
+            areas.graphs.insert(area_id, graph.clone());
+        }
+        graph
+    }
+
+    pub fn invalidate(&self, area_id: i32) {
+        let mut areas = self.areas.write().unwrap();
+        areas.graphs.remove(&area_id);
+        *areas.generations.entry(area_id).or_insert(0) += 1;
+    }
+
+    pub fn cached_areas(&self) -> usize {
+        self.areas.read().unwrap().graphs.len()
+    }
+
+    pub fn mark_warmed(&self) {
+        self.warmed.store(true, Ordering::Release);
+    }
+
+    pub fn is_warmed(&self) -> bool {
+        self.warmed.load(Ordering::Acquire)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_caches_graph_built_from_current_generation() {
+        let cache = GraphCache::new();
+
+        let generation = cache.generation(1);
+        cache.insert(1, generation, Graph::new());
+
+        assert!(cache.get(1).is_some());
+        assert_eq!(cache.cached_areas(), 1);
+    }
+
+    #[test]
+    fn insert_skips_graph_invalidated_during_build() {
+        let cache = GraphCache::new();
+
+        let generation = cache.generation(1);
+        // 構築中に辺が更新された
+        cache.invalidate(1);
+        let graph = cache.insert(1, generation, Graph::new());
+
+        assert!(graph.nodes.is_empty());
+        assert!(cache.get(1).is_none());
+
+        // 無効化の後に構築を始めたグラフはキャッシュする
+        let generation = cache.generation(1);
+        cache.insert(1, generation, Graph::new());
+        assert!(cache.get(1).is_some());
+    }
+
+    #[test]
+    fn invalidate_affects_only_its_area() {
+        let cache = GraphCache::new();
+        let generation = cache.generation(2);
+
+        cache.invalidate(1);
+        cache.insert(2, generation, Graph::new());
+
+        assert!(cache.get(2).is_some());
+    }
+}
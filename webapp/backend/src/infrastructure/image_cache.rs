@@ -0,0 +1,91 @@
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use actix_web::web::{self, Bytes};
+use log::{error, warn};
+use lru::LruCache;
+
+use crate::errors::AppError;
+
+// 生成済みの画像バリアントをメモリ（LRU）とディスクの二段でキャッシュする
+#[derive(Debug)]
+pub struct ImageCache {
+    memory: Mutex<LruCache<String, Bytes>>,
+    cache_dir: PathBuf,
+}
+
+impl ImageCache {
+    pub fn new(cache_dir: impl Into<PathBuf>, capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        ImageCache {
+            memory: Mutex::new(LruCache::new(capacity)),
+            cache_dir: cache_dir.into(),
+        }
+    }
+
+    pub async fn get_or_generate<F>(&self, key: &str, generate: F) -> Result<Bytes, AppError>
+    where
+        F: FnOnce() -> Result<Vec<u8>, AppError> + Send + 'static,
+    {
+        if let Some(bytes) = self.memory.lock().unwrap().get(key) {
+            return Ok(bytes.clone());
+        }
+
+        let disk_path = self.cache_dir.join(disk_file_name(key));
+
+        // ディスクの読み書きと画像の生成はブロッキング処理なので専用スレッドで行う
+        let bytes = web::block(move || -> Result<Bytes, AppError> {
+            if let Ok(cached) = fs::read(&disk_path) {
+                return Ok(Bytes::from(cached));
+            }
+
+            let generated = generate()?;
+            write_atomically(&disk_path, &generated);
+            Ok(Bytes::from(generated))
+        })
+        .await
+        .map_err(|e| {
+            error!("画像生成スレッドの実行に失敗しました: {:?}", e);
+            AppError::InternalServerError
+        })??;
+
+        self.memory
+            .lock()
+            .unwrap()
+            .put(key.to_string(), bytes.clone());
+
+        Ok(bytes)
+    }
+}
+
+fn disk_file_name(key: &str) -> String {
+    key.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// 書き込み途中のファイルを他のリクエストが読まないよう、一時ファイル経由で配置する
+fn write_atomically(path: &Path, bytes: &[u8]) {
+    let Some(dir) = path.parent() else {
+        return;
+    };
+    if let Err(e) = fs::create_dir_all(dir) {
+        warn!("キャッシュディレクトリの作成に失敗しました: {:?}", e);
+        return;
+    }
+
+    let tmp_path = path.with_extension(format!("tmp-{}", rand::random::<u32>()));
+    let result = fs::write(&tmp_path, bytes).and_then(|_| fs::rename(&tmp_path, path));
+    if let Err(e) = result {
+        warn!("キャッシュファイルの書き込みに失敗しました: {:?}", e);
+        let _ = fs::remove_file(&tmp_path);
+    }
+}
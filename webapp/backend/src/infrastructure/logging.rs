@@ -0,0 +1,170 @@
+use std::io;
+
+use tracing::Subscriber;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+use crate::config::{LogFormat, LoggingConfig};
+
+// log クレート経由のログも含めて tracing のサブスクライバに集約する
+pub fn init(config: &LoggingConfig) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&config.level));
+
+    subscriber(config, filter, io::stdout).init();
+}
+
+// 出力先を差し替えられるように、サブスクライバの組み立てを init() から分けておく
+pub fn subscriber<W>(
+    config: &LoggingConfig,
+    filter: EnvFilter,
+    writer: W,
+) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let span_events = if config.log_spans {
+        FmtSpan::CLOSE
+    } else {
+        FmtSpan::NONE
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(span_events)
+        .with_writer(writer);
+
+    match config.format {
+        LogFormat::Json => Box::new(
+            builder
+                .json()
+                .flatten_event(true)
+                .with_current_span(true)
+                .with_span_list(true)
+                .finish(),
+        ),
+        LogFormat::Text => Box::new(builder.finish()),
+    }
+}
+
+// テストで出力されたログを 1 行ずつ取り出すための書き込み先
+#[cfg(test)]
+pub mod capture {
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+
+    use tracing_subscriber::fmt::MakeWriter;
+
+    #[derive(Clone, Default)]
+    pub struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+    impl CapturedLogs {
+        pub fn lines(&self) -> Vec<String> {
+            let buffer = self.0.lock().unwrap();
+            String::from_utf8_lossy(&buffer)
+                .lines()
+                .map(str::to_string)
+                .collect()
+        }
+    }
+
+    impl Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for CapturedLogs {
+        type Writer = CapturedLogs;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use tracing::{info, info_span};
+
+    use super::capture::CapturedLogs;
+    use super::*;
+
+    fn config(format: LogFormat, log_spans: bool) -> LoggingConfig {
+        LoggingConfig {
+            level: "info".to_string(),
+            format,
+            log_spans,
+        }
+    }
+
+    #[test]
+    fn json_format_writes_one_object_per_line_with_span_fields() {
+        let logs = CapturedLogs::default();
+        let subscriber = subscriber(
+            &config(LogFormat::Json, false),
+            EnvFilter::new("info"),
+            logs.clone(),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = info_span!("request", request_id = "abc").entered();
+            info!(status = 200, "handled");
+        });
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 1);
+        let line: Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["message"], "handled");
+        assert_eq!(line["status"], 200);
+        assert_eq!(line["span"]["name"], "request");
+        assert_eq!(line["span"]["request_id"], "abc");
+    }
+
+    #[test]
+    fn filter_drops_lower_levels() {
+        let logs = CapturedLogs::default();
+        let subscriber = subscriber(
+            &config(LogFormat::Json, false),
+            EnvFilter::new("warn"),
+            logs.clone(),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            info!("dropped");
+            tracing::warn!("kept");
+        });
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains("kept"));
+    }
+
+    #[test]
+    fn log_spans_reports_span_close() {
+        let logs = CapturedLogs::default();
+        let subscriber = subscriber(
+            &config(LogFormat::Text, true),
+            EnvFilter::new("info"),
+            logs.clone(),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = info_span!("find_user").entered();
+        });
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains("find_user"));
+        assert!(lines[0].contains("close"));
+    }
+}
@@ -0,0 +1,215 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+
+use super::db::PoolStats;
+
+// Prometheus 形式で公開するアプリケーション全体のメトリクス
+pub struct Metrics {
+    registry: Registry,
+    http_requests_total: IntCounterVec,
+    http_request_duration_seconds: HistogramVec,
+    db_pool_connections: IntGaugeVec,
+    shortest_path_duration_seconds: Histogram,
+    shortest_path_visited_nodes: Histogram,
+    profile_image_render_duration_seconds: HistogramVec,
+    dispatches_total: IntCounterVec,
+}
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+pub fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(Metrics::new)
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+
+        let http_requests_total = IntCounterVec::new(
+            Opts::new("http_requests_total", "Number of HTTP requests handled"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_request_duration_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency in seconds",
+            ),
+            &["method", "route"],
+        )
+        .unwrap();
+        let db_pool_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Number of database pool connections"),
+            &["state"],
+        )
+        .unwrap();
+        let shortest_path_duration_seconds = Histogram::with_opts(
+            HistogramOpts::new(
+                "shortest_path_duration_seconds",
+                "Time spent computing a shortest path in seconds",
+            )
+            .buckets(vec![
+                0.00001, 0.00005, 0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5,
+            ]),
+        )
+        .unwrap();
+        let shortest_path_visited_nodes = Histogram::with_opts(
+            HistogramOpts::new(
+                "shortest_path_visited_nodes",
+                "Number of nodes visited while computing a shortest path",
+            )
+            .buckets(prometheus::exponential_buckets(1.0, 4.0, 10).unwrap()),
+        )
+        .unwrap();
+        let profile_image_render_duration_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "profile_image_render_duration_seconds",
+                "Time spent resizing and encoding a profile image in seconds",
+            ),
+            &["format"],
+        )
+        .unwrap();
+        let dispatches_total = IntCounterVec::new(
+            Opts::new("dispatches_total", "Number of tow truck dispatch attempts"),
+            &["result"],
+        )
+        .unwrap();
+
+        registry
+            .register(Box::new(http_requests_total.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(http_request_duration_seconds.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(db_pool_connections.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(shortest_path_duration_seconds.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(shortest_path_visited_nodes.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(profile_image_render_duration_seconds.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(dispatches_total.clone()))
+            .unwrap();
+
+        Metrics {
+            registry,
+            http_requests_total,
+            http_request_duration_seconds,
+            db_pool_connections,
+            shortest_path_duration_seconds,
+            shortest_path_visited_nodes,
+            profile_image_render_duration_seconds,
+            dispatches_total,
+        }
+    }
+
+    pub fn observe_http_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        self.http_requests_total
+            .with_label_values(&[method, route, &status.to_string()])
+            .inc();
+        self.http_request_duration_seconds
+            .with_label_values(&[method, route])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn set_db_pool_stats(&self, stats: &PoolStats) {
+        self.db_pool_connections
+            .with_label_values(&["size"])
+            .set(stats.size as i64);
+        self.db_pool_connections
+            .with_label_values(&["idle"])
+            .set(stats.idle as i64);
+        self.db_pool_connections
+            .with_label_values(&["in_use"])
+            .set(stats.in_use as i64);
+        self.db_pool_connections
+            .with_label_values(&["max"])
+            .set(stats.max_connections as i64);
+    }
+
+    pub fn observe_shortest_path(&self, elapsed: Duration, visited_nodes: usize) {
+        self.shortest_path_duration_seconds
+            .observe(elapsed.as_secs_f64());
+        self.shortest_path_visited_nodes
+            .observe(visited_nodes as f64);
+    }
+
+    pub fn observe_profile_image_render(&self, format: &str, elapsed: Duration) {
+        self.profile_image_render_duration_seconds
+            .with_label_values(&[format])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn record_dispatch(&self, succeeded: bool) {
+        let result = if succeeded { "success" } else { "failure" };
+        self.dispatches_total.with_label_values(&[result]).inc();
+    }
+
+    pub fn encode(&self) -> Result<String, prometheus::Error> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_includes_observed_values() {
+        let metrics = Metrics::new();
+
+        metrics.observe_http_request("GET", "/api/order/{id}", 200, Duration::from_millis(5));
+        metrics.observe_http_request("GET", "/api/order/{id}", 200, Duration::from_millis(7));
+        metrics.record_dispatch(true);
+        metrics.record_dispatch(false);
+        metrics.record_dispatch(false);
+        metrics.observe_shortest_path(Duration::from_micros(30), 12);
+
+        let text = metrics.encode().unwrap();
+
+        assert!(text.contains(
+            r#"http_requests_total{method="GET",route="/api/order/{id}",status="200"} 2"#
+        ));
+        assert!(text.contains(
+            r#"http_request_duration_seconds_count{method="GET",route="/api/order/{id}"} 2"#
+        ));
+        assert!(text.contains(r#"dispatches_total{result="success"} 1"#));
+        assert!(text.contains(r#"dispatches_total{result="failure"} 2"#));
+        assert!(text.contains("shortest_path_visited_nodes_sum 12"));
+        assert!(text.contains("shortest_path_duration_seconds_count 1"));
+    }
+
+    #[test]
+    fn set_db_pool_stats_overwrites_gauges() {
+        let metrics = Metrics::new();
+        let stats = |size, idle| PoolStats {
+            size,
+            idle,
+            in_use: (size as usize) - idle,
+            max_connections: 10,
+            is_closed: false,
+        };
+
+        metrics.set_db_pool_stats(&stats(5, 1));
+        metrics.set_db_pool_stats(&stats(3, 2));
+        let text = metrics.encode().unwrap();
+
+        assert!(text.contains(r#"db_pool_connections{state="size"} 3"#));
+        assert!(text.contains(r#"db_pool_connections{state="idle"} 2"#));
+        assert!(text.contains(r#"db_pool_connections{state="in_use"} 1"#));
+        assert!(text.contains(r#"db_pool_connections{state="max"} 10"#));
+    }
+}
@@ -0,0 +1,391 @@
+use log::info;
+use sha2::{Digest, Sha256};
+use sqlx::mysql::{MySqlConnection, MySqlPool};
+use sqlx::{Executor, Row};
+use thiserror::Error;
+
+// 複数のインスタンスが同時に起動しても、マイグレーションは 1 つずつ適用されるようにする
+const MIGRATION_LOCK_NAME: &str = "schema_migrations";
+const MIGRATION_LOCK_TIMEOUT_SECS: i32 = 60;
+
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+    // マイグレーションで作成されるテーブル・インデックス・カラム
+    // schema_migrations に記録が無くてもすべて存在する場合は、実行せずに適用済みとして記録する
+    pub creates: &'static [SchemaObject],
+}
+
+pub enum SchemaObject {
+    Table(&'static str),
+    Index {
+        table: &'static str,
+        name: &'static str,
+    },
+    Column {
+        table: &'static str,
+        name: &'static str,
+    },
+}
+
+// バイナリに埋め込むマイグレーション (version の昇順に並べること)
+// MySQL の DDL は暗黙にコミットされ途中で失敗すると巻き戻せないため、1 つのマイグレーションには DDL を 1 文だけ書く
+// mysql/migration/ の SQL は採点前にスクリプトから適用されるため、ここには含めない
+// (1〜3 は以前 mysql/migration/ にあったため、スクリプトで適用済みの DB もある)
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "password_reset_tokens",
+        sql: include_str!("../../migrations/1_password_reset_tokens.sql"),
+        creates: &[SchemaObject::Table("password_reset_tokens")],
+    },
+    Migration {
+        version: 2,
+        name: "session_created_at",
+        sql: include_str!("../../migrations/2_session_created_at.sql"),
+        creates: &[SchemaObject::Column {
+            table: "sessions",
+            name: "created_at",
+        }],
+    },
+    Migration {
+        version: 3,
+        name: "sessions_user_id_index",
+        sql: include_str!("../../migrations/3_sessions_user_id_index.sql"),
+        creates: &[SchemaObject::Index {
+            table: "sessions",
+            name: "idx_sessions_user_id",
+        }],
+    },
+    Migration {
+        version: 4,
+        name: "driver_shifts",
+        sql: include_str!("../../migrations/4_driver_shifts.sql"),
+        creates: &[SchemaObject::Table("driver_shifts")],
+    },
+    Migration {
+        version: 5,
+        name: "driver_shifts_driver_id_index",
+        sql: include_str!("../../migrations/5_driver_shifts_driver_id_index.sql"),
+        creates: &[SchemaObject::Index {
+            table: "driver_shifts",
+            name: "idx_driver_shifts_driver_id_started_at",
+        }],
+    },
+    Migration {
+        version: 6,
+        name: "driver_shifts_area_id_index",
+        sql: include_str!("../../migrations/6_driver_shifts_area_id_index.sql"),
+        creates: &[SchemaObject::Index {
+            table: "driver_shifts",
+            name: "idx_driver_shifts_area_id_ended_at",
+        }],
+    },
+    Migration {
+        version: 7,
+        name: "edge_closures",
+        sql: include_str!("../../migrations/7_edge_closures.sql"),
+        creates: &[SchemaObject::Column {
+            table: "edges",
+            name: "is_closed",
+        }],
+    },
+];
+
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    #[error("database error while migrating: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("could not acquire the migration lock within {0} seconds")]
+    LockTimeout(i32),
+    #[error("database schema version {applied} is newer than the latest migration {latest} known to this binary")]
+    SchemaAhead { applied: i64, latest: i64 },
+    #[error("migration {version} ({name}) was modified after it was applied")]
+    ChecksumMismatch { version: i64, name: String },
+    #[error("database schema is behind this binary (pending migrations: {0:?}); run `backend migrate` or enable database.migrate_on_startup")]
+    Pending(Vec<i64>),
+}
+
+struct AppliedMigration {
+    version: i64,
+    checksum: String,
+}
+
+fn checksum(sql: &str) -> String {
+    Sha256::digest(sql.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+// 未適用のマイグレーションを適用する。apply が false の場合はスキーマが最新かどうかの確認だけを行う
+// 適用したマイグレーションの version を返す
+pub async fn run_migrations(pool: &MySqlPool, apply: bool) -> Result<Vec<i64>, MigrationError> {
+    let mut conn = pool.acquire().await?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version BIGINT PRIMARY KEY,
+            name VARCHAR(255) NOT NULL,
+            checksum CHAR(64) NOT NULL,
+            applied_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+    )
+    .await?;
+
+    let locked: Option<i64> = sqlx::query_scalar("SELECT GET_LOCK(?, ?)")
+        .bind(MIGRATION_LOCK_NAME)
+        .bind(MIGRATION_LOCK_TIMEOUT_SECS)
+        .fetch_one(&mut *conn)
+        .await?;
+    if locked != Some(1) {
+        return Err(MigrationError::LockTimeout(MIGRATION_LOCK_TIMEOUT_SECS));
+    }
+
+    let result = run_locked(&mut conn, apply).await;
+
+    sqlx::query("SELECT RELEASE_LOCK(?)")
+        .bind(MIGRATION_LOCK_NAME)
+        .execute(&mut *conn)
+        .await?;
+
+    result
+}
+
+async fn run_locked(conn: &mut MySqlConnection, apply: bool) -> Result<Vec<i64>, MigrationError> {
+    let applied: Vec<AppliedMigration> =
+        sqlx::query("SELECT version, checksum FROM schema_migrations ORDER BY version")
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|row| AppliedMigration {
+                version: row.get("version"),
+                checksum: row.get("checksum"),
+            })
+            .collect();
+
+    let pending = plan(&applied, MIGRATIONS, apply)?;
+
+    let mut applied_versions = Vec::new();
+    for migration in pending {
+        if schema_exists(conn, migration.creates).await? {
+            info!(
+                "マイグレーション {}_{} の変更は既に DB にあるため、適用済みとして記録します",
+                migration.version, migration.name
+            );
+        } else {
+            info!(
+                "マイグレーション {}_{} を適用します",
+                migration.version, migration.name
+            );
+            // MySQL の DDL はトランザクションで巻き戻せないため、成功した後に記録する
+            conn.execute(migration.sql).await?;
+        }
+        sqlx::query("INSERT INTO schema_migrations (version, name, checksum) VALUES (?, ?, ?)")
+            .bind(migration.version)
+            .bind(migration.name)
+            .bind(checksum(migration.sql))
+            .execute(&mut *conn)
+            .await?;
+        applied_versions.push(migration.version);
+    }
+
+    Ok(applied_versions)
+}
+
+// 未適用のマイグレーションを返す。apply が false の場合は未適用があればエラーにする
+fn plan<'a>(
+    applied: &[AppliedMigration],
+    migrations: &'a [Migration],
+    apply: bool,
+) -> Result<Vec<&'a Migration>, MigrationError> {
+    // DB が新しいバイナリで移行済みの場合、古いバイナリで動かすとスキーマと食い違う
+    let latest = migrations.last().map(|m| m.version).unwrap_or(0);
+    if let Some(newest) = applied.iter().map(|m| m.version).max() {
+        if newest > latest {
+            return Err(MigrationError::SchemaAhead {
+                applied: newest,
+                latest,
+            });
+        }
+    }
+
+    let mut pending = Vec::new();
+    for migration in migrations {
+        match applied.iter().find(|m| m.version == migration.version) {
+            Some(applied) if applied.checksum != checksum(migration.sql) => {
+                return Err(MigrationError::ChecksumMismatch {
+                    version: migration.version,
+                    name: migration.name.to_string(),
+                });
+            }
+            Some(_) => {}
+            None => pending.push(migration),
+        }
+    }
+
+    if !apply && !pending.is_empty() {
+        return Err(MigrationError::Pending(
+            pending.iter().map(|m| m.version).collect(),
+        ));
+    }
+
+    Ok(pending)
+}
+
+async fn schema_exists(
+    conn: &mut MySqlConnection,
+    objects: &[SchemaObject],
+) -> Result<bool, sqlx::Error> {
+    if objects.is_empty() {
+        return Ok(false);
+    }
+
+    for object in objects {
+        let query = match object {
+            SchemaObject::Table(table) => sqlx::query_scalar(
+                "SELECT COUNT(*) FROM information_schema.tables
+                 WHERE table_schema = DATABASE() AND table_name = ?",
+            )
+            .bind(*table),
+            SchemaObject::Index { table, name } => sqlx::query_scalar(
+                "SELECT COUNT(*) FROM information_schema.statistics
+                 WHERE table_schema = DATABASE() AND table_name = ? AND index_name = ?",
+            )
+            .bind(*table)
+            .bind(*name),
+            SchemaObject::Column { table, name } => sqlx::query_scalar(
+                "SELECT COUNT(*) FROM information_schema.columns
+                 WHERE table_schema = DATABASE() AND table_name = ? AND column_name = ?",
+            )
+            .bind(*table)
+            .bind(*name),
+        };
+        let count: i64 = query.fetch_one(&mut *conn).await?;
+        if count == 0 {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_MIGRATIONS: &[Migration] = &[
+        Migration {
+            version: 1,
+            name: "first",
+            sql: "CREATE TABLE first (id INT);",
+            creates: &[SchemaObject::Table("first")],
+        },
+        Migration {
+            version: 2,
+            name: "second",
+            sql: "CREATE TABLE second (id INT);",
+            creates: &[SchemaObject::Table("second")],
+        },
+    ];
+
+    fn applied(version: i64, sql: &str) -> AppliedMigration {
+        AppliedMigration {
+            version,
+            checksum: checksum(sql),
+        }
+    }
+
+    fn versions(migrations: Vec<&Migration>) -> Vec<i64> {
+        migrations.iter().map(|m| m.version).collect()
+    }
+
+    #[test]
+    fn plan_returns_unapplied_migrations_in_order() {
+        let applied = [applied(1, TEST_MIGRATIONS[0].sql)];
+
+        let pending = plan(&applied, TEST_MIGRATIONS, true).unwrap();
+
+        assert_eq!(versions(pending), vec![2]);
+        assert_eq!(
+            versions(plan(&[], TEST_MIGRATIONS, true).unwrap()),
+            vec![1, 2]
+        );
+    }
+
+    #[test]
+    fn plan_reports_pending_when_not_applying() {
+        let applied = [applied(1, TEST_MIGRATIONS[0].sql)];
+
+        match plan(&applied, TEST_MIGRATIONS, false) {
+            Err(MigrationError::Pending(versions)) => assert_eq!(versions, vec![2]),
+            other => panic!("unexpected result: {:?}", other.map(versions)),
+        }
+    }
+
+    #[test]
+    fn plan_accepts_up_to_date_schema_when_not_applying() {
+        let applied = [
+            applied(1, TEST_MIGRATIONS[0].sql),
+            applied(2, TEST_MIGRATIONS[1].sql),
+        ];
+
+        assert!(plan(&applied, TEST_MIGRATIONS, false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn plan_rejects_schema_ahead_of_binary() {
+        let applied = [
+            applied(1, TEST_MIGRATIONS[0].sql),
+            applied(2, TEST_MIGRATIONS[1].sql),
+            applied(3, "ALTER TABLE first ADD COLUMN name TEXT;"),
+        ];
+
+        match plan(&applied, TEST_MIGRATIONS, true) {
+            Err(MigrationError::SchemaAhead { applied, latest }) => {
+                assert_eq!((applied, latest), (3, 2));
+            }
+            other => panic!("unexpected result: {:?}", other.map(versions)),
+        }
+    }
+
+    #[test]
+    fn plan_rejects_modified_migration() {
+        let applied = [applied(1, "CREATE TABLE first (id BIGINT);")];
+
+        match plan(&applied, TEST_MIGRATIONS, true) {
+            Err(MigrationError::ChecksumMismatch { version, name }) => {
+                assert_eq!((version, name.as_str()), (1, "first"));
+            }
+            other => panic!("unexpected result: {:?}", other.map(versions)),
+        }
+    }
+
+    #[test]
+    fn embedded_migrations_are_ordered_and_describe_their_schema() {
+        let versions: Vec<i64> = MIGRATIONS.iter().map(|m| m.version).collect();
+        let mut sorted = versions.clone();
+        sorted.sort();
+        sorted.dedup();
+
+        assert_eq!(versions, sorted);
+        // 既存の DB に適用済みかを判定できるようにする
+        assert!(MIGRATIONS.iter().all(|m| !m.creates.is_empty()));
+    }
+
+    #[test]
+    fn embedded_migrations_contain_a_single_statement() {
+        for migration in MIGRATIONS {
+            let statements = migration
+                .sql
+                .lines()
+                .filter(|line| !line.trim_start().starts_with("--"))
+                .collect::<String>()
+                .matches(';')
+                .count();
+            assert_eq!(statements, 1, "{}_{}", migration.version, migration.name);
+            assert_eq!(migration.creates.len(), 1);
+        }
+    }
+}
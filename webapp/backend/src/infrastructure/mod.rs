@@ -1 +1,7 @@
+pub mod background_tasks;
 pub mod db;
+pub mod graph_cache;
+pub mod image_cache;
+pub mod logging;
+pub mod metrics;
+pub mod migrations;
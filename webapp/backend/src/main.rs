@@ -1,19 +1,40 @@
+use std::env;
+use std::process;
 use std::sync::Arc;
 
 use actix_cors::Cors;
 use actix_web::{web, App, HttpServer};
-use api::{auth_handler, health_check_handler, map_handler, order_handler, tow_truck_handler};
+use api::{
+    auth_handler, driver_handler, health_check_handler, map_handler, order_handler,
+    tow_truck_handler,
+};
+use config::AppConfig;
+use domains::driver_service::DriverService;
+use domains::eta_service::EtaService;
 use domains::map_service::MapService;
+use domains::order_events::OrderEventBus;
+use domains::tow_truck_events::TowTruckEventBus;
 use domains::{
     auth_service::AuthService, order_service::OrderService, tow_truck_service::TowTruckService,
 };
+use errors::AppError;
+use infrastructure::background_tasks::BackgroundTasks;
+use infrastructure::graph_cache::GraphCache;
+use infrastructure::migrations::run_migrations;
+use log::{error, info, warn};
+use middlewares::access_log_middleware::AccessLogMiddleware;
 use middlewares::auth_middleware::AuthMiddleware;
+use middlewares::metrics_middleware::MetricsMiddleware;
+use middlewares::rate_limit_middleware::{RateLimitMiddleware, RateLimiter};
+use middlewares::request_id_middleware::RequestIdMiddleware;
 use repositories::auth_repository::AuthRepositoryImpl;
 use repositories::map_repository::MapRepositoryImpl;
 use repositories::order_repository::OrderRepositoryImpl;
 use repositories::tow_truck_repository::TowTruckRepositoryImpl;
+use utils::TrustedProxies;
 
 mod api;
+mod config;
 mod domains;
 mod errors;
 mod infrastructure;
@@ -22,34 +43,191 @@ mod models;
 mod repositories;
 mod utils;
 
+const USAGE: &str = "usage: backend [serve|migrate|grant-admin <username>]";
+
+enum Command {
+    Serve,
+    Migrate,
+    // 管理者は登録 API では作成できないため、サーバー上でこのコマンドから付与する
+    GrantAdmin(String),
+}
+
+fn parse_command() -> Command {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] | ["serve"] => Command::Serve,
+        ["migrate"] => Command::Migrate,
+        ["grant-admin", username] => Command::GrantAdmin(username.to_string()),
+        _ => {
+            eprintln!("unknown command: {}\n{USAGE}", args.join(" "));
+            process::exit(2);
+        }
+    }
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let pool = infrastructure::db::create_pool().await;
-    let mut port = 8080;
+    let command = parse_command();
+    let config = match AppConfig::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}");
+            process::exit(1);
+        }
+    };
+    infrastructure::logging::init(&config.logging);
+
+    let pool = match infrastructure::db::create_pool(&config.database).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            error!("failed to connect to database: {e}");
+            process::exit(1);
+        }
+    };
 
-    if cfg!(debug_assertions) {
-        port = 18080;
+    let apply_migrations = match command {
+        Command::Migrate => true,
+        Command::Serve | Command::GrantAdmin(_) => config.database.migrate_on_startup,
+    };
+    match run_migrations(&pool, apply_migrations).await {
+        Ok(applied) if !applied.is_empty() => {
+            info!("マイグレーションを適用しました: {:?}", applied)
+        }
+        Ok(_) => {}
+        Err(e) => {
+            error!("{e}");
+            process::exit(1);
+        }
+    }
+    if let Command::Migrate = command {
+        pool.close().await;
+        return Ok(());
     }
 
-    let auth_service = web::Data::new(AuthService::new(AuthRepositoryImpl::new(pool.clone())));
-    let auth_service_for_middleware =
-        Arc::new(AuthService::new(AuthRepositoryImpl::new(pool.clone())));
+    let auth_service = web::Data::new(AuthService::new(
+        AuthRepositoryImpl::new(pool.clone()),
+        &config.images,
+        &config.session,
+    ));
+    if let Command::GrantAdmin(username) = &command {
+        let result = auth_service.grant_admin(username).await;
+        pool.close().await;
+        return match result {
+            Ok(()) => {
+                info!("{} に管理者ロールを付与しました", username);
+                Ok(())
+            }
+            Err(e) => {
+                error!("{} に管理者ロールを付与できませんでした: {}", username, e);
+                process::exit(1);
+            }
+        };
+    }
+    let auth_service_for_middleware = Arc::new(AuthService::new(
+        AuthRepositoryImpl::new(pool.clone()),
+        &config.images,
+        &config.session,
+    ));
+    let graph_cache = Arc::new(GraphCache::new());
+    let tow_truck_events = Arc::new(TowTruckEventBus::new());
+    let order_events = Arc::new(OrderEventBus::new());
+    let eta_service = Arc::new(EtaService::new(&config.eta, graph_cache.clone()));
     let tow_truck_service = web::Data::new(TowTruckService::new(
         TowTruckRepositoryImpl::new(pool.clone()),
         OrderRepositoryImpl::new(pool.clone()),
         MapRepositoryImpl::new(pool.clone()),
+        graph_cache.clone(),
+        tow_truck_events.clone(),
+        eta_service.clone(),
     ));
     let order_service = web::Data::new(OrderService::new(
         OrderRepositoryImpl::new(pool.clone()),
         TowTruckRepositoryImpl::new(pool.clone()),
         AuthRepositoryImpl::new(pool.clone()),
         MapRepositoryImpl::new(pool.clone()),
+        order_events.clone(),
+        tow_truck_events.clone(),
+        eta_service.clone(),
+    ));
+    let driver_service = web::Data::new(DriverService::new(
+        TowTruckRepositoryImpl::new(pool.clone()),
+        OrderRepositoryImpl::new(pool.clone()),
+        MapRepositoryImpl::new(pool.clone()),
+        graph_cache.clone(),
+        order_events.clone(),
+        tow_truck_events.clone(),
     ));
-    let map_service = web::Data::new(MapService::new(MapRepositoryImpl::new(pool.clone())));
+    let map_service = web::Data::new(MapService::new(
+        MapRepositoryImpl::new(pool.clone()),
+        graph_cache.clone(),
+    ));
+
+    let mut background_tasks = BackgroundTasks::new();
+
+    let auth_service_for_cleanup = auth_service.clone();
+    background_tasks.spawn_periodic(
+        "session_cleanup",
+        config.background.session_cleanup_interval(),
+        move || {
+            let auth_service = auth_service_for_cleanup.clone();
+            async move {
+                match auth_service.cleanup_sessions().await {
+                    Ok(0) => {}
+                    Ok(deleted) => info!("{} 件の不要なセッションを削除しました", deleted),
+                    Err(e) => warn!("セッションの削除に失敗しました: {:?}", e),
+                }
+            }
+        },
+    );
+
+    // 起動直後の実行でグラフキャッシュを温め、以降は定期的に DB の内容と同期する
+    let map_service_for_refresh = map_service.clone();
+    background_tasks.spawn_periodic(
+        "graph_cache_refresh",
+        config.background.graph_cache_refresh_interval(),
+        move || {
+            let map_service = map_service_for_refresh.clone();
+            async move {
+                if let Err(e) = map_service.refresh_graph_cache().await {
+                    warn!("グラフキャッシュの更新に失敗しました: {:?}", e);
+                }
+            }
+        },
+    );
 
-    HttpServer::new(move || {
+    let login_rate_limiter = Arc::new(RateLimiter::new(
+        config.rate_limit.login_max_requests,
+        config.rate_limit.window(),
+    ));
+    let register_rate_limiter = Arc::new(RateLimiter::new(
+        config.rate_limit.register_max_requests,
+        config.rate_limit.window(),
+    ));
+
+    // validate() 済みなので解析には失敗しない
+    let trusted_proxies =
+        web::Data::new(TrustedProxies::parse(&config.server.trusted_proxies).unwrap_or_default());
+
+    let cors_config = config.cors.clone();
+    let database_config = config.database.clone();
+    let pool_for_app = pool.clone();
+    let image_config = config.images.clone();
+    let mut server = HttpServer::new(move || {
         let mut cors = Cors::default();
 
+        for origin in &cors_config.allowed_origins {
+            cors = if origin == "*" {
+                cors.allow_any_origin()
+            } else {
+                cors.allowed_origin(origin)
+            };
+        }
+
         cors = cors
             .allowed_methods(vec!["GET", "POST", "PUT", "DELETE"])
             .allowed_headers(vec![
@@ -58,30 +236,65 @@ async fn main() -> std::io::Result<()> {
             ])
             .allowed_header(actix_web::http::header::CONTENT_TYPE)
             .supports_credentials()
-            .max_age(3600);
+            .max_age(cors_config.max_age_secs);
 
         App::new()
+            .app_data(web::Data::new(pool_for_app.clone()))
+            .app_data(web::Data::new(database_config.clone()))
+            .app_data(web::Data::new(image_config.clone()))
+            .app_data(web::Data::from(graph_cache.clone()))
+            .app_data(trusted_proxies.clone())
             .app_data(tow_truck_service.clone())
             .app_data(auth_service.clone())
             .app_data(order_service.clone())
+            .app_data(driver_service.clone())
             .app_data(map_service.clone())
+            .app_data(web::JsonConfig::default().error_handler(|_, _| AppError::BadRequest.into()))
+            .app_data(web::QueryConfig::default().error_handler(|_, _| AppError::BadRequest.into()))
+            .app_data(web::PathConfig::default().error_handler(|_, _| AppError::BadRequest.into()))
+            .wrap(AccessLogMiddleware)
+            .wrap(MetricsMiddleware)
             .wrap(cors)
+            .wrap(RequestIdMiddleware)
+            .service(
+                web::resource("/metrics")
+                    .route(web::get().to(health_check_handler::metrics_handler)),
+            )
             .service(
                 web::scope("/api")
                     .service(
                         web::resource("/health_check")
                             .route(web::get().to(health_check_handler::health_check_handler)),
                     )
+                    .service(
+                        web::resource("/health/live")
+                            .route(web::get().to(health_check_handler::health_check_handler)),
+                    )
+                    .service(
+                        web::resource("/health/ready")
+                            .route(web::get().to(health_check_handler::readiness_handler)),
+                    )
+                    .service(
+                        // プールの利用状況は内部情報なので、ログイン済みのユーザーにだけ返す
+                        web::resource("/health_check/db_pool")
+                            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
+                            .route(web::get().to(health_check_handler::db_pool_stats_handler)),
+                    )
                     .service(
                         web::resource("/validate_session")
                             .route(web::get().to(auth_handler::validate_session_handler)),
                     )
                     .service(
                         web::resource("/register")
+                            .wrap(RateLimitMiddleware::new(register_rate_limiter.clone()))
                             .route(web::post().to(auth_handler::register_handler)),
                     )
                     .service(
-                        web::resource("/login").route(web::post().to(auth_handler::login_handler)),
+                        web::resource("/login")
+                            .wrap(RateLimitMiddleware::failures_only(
+                                login_rate_limiter.clone(),
+                            ))
+                            .route(web::post().to(auth_handler::login_handler)),
                     )
                     .service(
                         web::resource("/logout")
@@ -91,6 +304,38 @@ async fn main() -> std::io::Result<()> {
                         web::resource("/user_image/{user_id}")
                             .route(web::get().to(auth_handler::user_profile_image_handler)),
                     )
+                    .service(
+                        web::resource("/password_reset")
+                            .route(web::post().to(auth_handler::reset_password_handler)),
+                    )
+                    .service(
+                        web::scope("/me")
+                            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
+                            .service(
+                                web::resource("/password")
+                                    .route(web::post().to(auth_handler::change_password_handler)),
+                            )
+                            .service(
+                                web::resource("/profile_image").route(
+                                    web::post().to(auth_handler::upload_profile_image_handler),
+                                ),
+                            )
+                            .service(
+                                web::resource("/orders")
+                                    .route(web::get().to(order_handler::get_my_orders_handler)),
+                            )
+                            .service(
+                                web::resource("/orders/{id}/repeat")
+                                    .route(web::post().to(order_handler::repeat_order_handler)),
+                            ),
+                    )
+                    .service(
+                        web::scope("/admin")
+                            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
+                            .service(web::resource("/users/{user_id}/password_reset").route(
+                                web::post().to(auth_handler::issue_password_reset_token_handler),
+                            )),
+                    )
                     .service(
                         web::scope("/tow_truck")
                             .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
@@ -102,6 +347,12 @@ async fn main() -> std::io::Result<()> {
                                     web::post().to(tow_truck_handler::update_location_handler),
                                 ),
                             )
+                            .service(web::resource("/positions").route(
+                                web::get().to(tow_truck_handler::tow_truck_positions_handler),
+                            ))
+                            .service(web::resource("/on_shift").route(
+                                web::get().to(tow_truck_handler::get_on_shift_drivers_handler),
+                            ))
                             .service(web::resource("/nearest").route(
                                 web::get().to(
                                     tow_truck_handler::get_nearest_available_tow_trucks_handler,
@@ -115,6 +366,10 @@ async fn main() -> std::io::Result<()> {
                     .service(
                         web::scope("/order")
                             .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
+                            .service(
+                                web::resource("/events")
+                                    .route(web::get().to(order_handler::order_events_handler)),
+                            )
                             .service(
                                 web::resource("/list").route(
                                     web::get().to(order_handler::get_paginated_orders_handler),
@@ -136,6 +391,39 @@ async fn main() -> std::io::Result<()> {
                             .service(
                                 web::resource("/{id}")
                                     .route(web::get().to(order_handler::get_order_handler)),
+                            )
+                            .service(
+                                web::resource("/{id}/tracking").route(
+                                    web::get().to(order_handler::get_order_tracking_handler),
+                                ),
+                            ),
+                    )
+                    .service(
+                        web::scope("/driver")
+                            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
+                            .service(
+                                web::resource("/current_job")
+                                    .route(web::get().to(driver_handler::get_current_job_handler)),
+                            )
+                            .service(
+                                web::resource("/jobs")
+                                    .route(web::get().to(driver_handler::get_jobs_handler)),
+                            )
+                            .service(
+                                web::resource("/jobs/{order_id}/{action}")
+                                    .route(web::post().to(driver_handler::update_job_handler)),
+                            )
+                            .service(
+                                web::resource("/shift/start")
+                                    .route(web::post().to(driver_handler::start_shift_handler)),
+                            )
+                            .service(
+                                web::resource("/shift/end")
+                                    .route(web::post().to(driver_handler::end_shift_handler)),
+                            )
+                            .service(
+                                web::resource("/shifts")
+                                    .route(web::get().to(driver_handler::get_shifts_handler)),
                             ),
                     )
                     .service(
@@ -144,12 +432,40 @@ async fn main() -> std::io::Result<()> {
                             .service(
                                 web::resource("/update_edge")
                                     .route(web::put().to(map_handler::update_edge_handler)),
+                            )
+                            .service(
+                                web::resource("/update_edges")
+                                    .route(web::put().to(map_handler::update_edges_handler)),
+                            )
+                            .service(
+                                web::resource("/close_edge")
+                                    .route(web::put().to(map_handler::close_edge_handler)),
+                            )
+                            .service(
+                                web::resource("/reopen_edge")
+                                    .route(web::put().to(map_handler::reopen_edge_handler)),
                             ),
                     ),
             )
-    })
-    .bind(format!("0.0.0.0:{port}"))?
-    // .workers(10)
-    .run()
-    .await
+    });
+
+    if let Some(workers) = config.server.workers {
+        server = server.workers(workers);
+    }
+
+    // SIGTERM / SIGINT を受けると新規接続の受付を止め、処理中のリクエストを待ってから run が返る
+    let result = server
+        .shutdown_timeout(config.server.shutdown_timeout_secs)
+        .bind(config.server.bind_addr())?
+        .run()
+        .await;
+
+    info!("バックグラウンドタスクを停止しています");
+    background_tasks
+        .shutdown(config.server.shutdown_timeout())
+        .await;
+    pool.close().await;
+    info!("シャットダウンが完了しました");
+
+    result
 }
@@ -0,0 +1,168 @@
+use std::time::Instant;
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpMessage,
+};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+use tracing::{info, info_span, Instrument};
+
+use crate::middlewares::request_id_middleware::current_request_id;
+use crate::models::user::Session;
+
+// 1 リクエストにつき 1 行のアクセスログを出力し、以降の処理をリクエスト単位のスパンで囲む
+// RequestIdMiddleware より内側に置くこと (リクエスト ID を参照するため)
+pub struct AccessLogMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for AccessLogMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = AccessLogMiddlewareMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AccessLogMiddlewareMiddleware { service }))
+    }
+}
+
+pub struct AccessLogMiddlewareMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for AccessLogMiddlewareMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let started_at = Instant::now();
+        let method = req.method().to_string();
+        let path = req.path().to_string();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let request_id = current_request_id().unwrap_or_default();
+            let span = info_span!("request", %request_id, %method, %path);
+
+            let result = fut.instrument(span).await;
+
+            // リクエストを clone して保持するとルーティング時に panic するため、
+            // 認証ミドルウェアが登録したセッションはレスポンスに紐づくリクエストから参照する
+            let (status, user_id) = match &result {
+                Ok(res) => (
+                    res.status(),
+                    res.request()
+                        .extensions()
+                        .get::<Session>()
+                        .map(|session| session.user_id),
+                ),
+                Err(err) => (err.as_response_error().status_code(), None),
+            };
+
+            info!(
+                target: "access_log",
+                %request_id,
+                %method,
+                %path,
+                status = status.as_u16(),
+                latency_ms = started_at.elapsed().as_secs_f64() * 1000.0,
+                user_id,
+            );
+
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, web, App, HttpRequest, HttpResponse};
+    use chrono::Utc;
+    use serde_json::Value;
+    use tracing_subscriber::EnvFilter;
+
+    use super::*;
+    use crate::config::{LogFormat, LoggingConfig};
+    use crate::infrastructure::logging::{capture::CapturedLogs, subscriber};
+
+    // 認証ミドルウェアの代わりにハンドラでセッションを登録する
+    async fn authenticated(req: HttpRequest) -> HttpResponse {
+        req.extensions_mut().insert(Session {
+            id: 1,
+            user_id: 42,
+            session_token: "token".to_string(),
+            is_valid: true,
+            created_at: Utc::now(),
+        });
+        HttpResponse::Ok().finish()
+    }
+
+    async fn access_logs(uri: &str) -> Vec<Value> {
+        let logs = CapturedLogs::default();
+        let _guard = tracing::subscriber::set_default(subscriber(
+            &LoggingConfig {
+                level: "info".to_string(),
+                format: LogFormat::Json,
+                log_spans: false,
+            },
+            EnvFilter::new("info"),
+            logs.clone(),
+        ));
+
+        let app = test::init_service(
+            App::new()
+                .wrap(AccessLogMiddleware)
+                .route("/me", web::get().to(authenticated))
+                .route("/public", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+        test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+
+        logs.lines()
+            .iter()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .filter(|line| line["target"] == "access_log")
+            .collect()
+    }
+
+    #[actix_rt::test]
+    async fn writes_one_access_log_per_request() {
+        let logs = access_logs("/public?page=1").await;
+
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0]["method"], "GET");
+        assert_eq!(logs[0]["path"], "/public");
+        assert_eq!(logs[0]["status"], 200);
+        assert!(logs[0]["latency_ms"].as_f64().unwrap() >= 0.0);
+        assert!(logs[0].get("user_id").is_none());
+    }
+
+    #[actix_rt::test]
+    async fn access_log_includes_authenticated_user() {
+        let logs = access_logs("/me").await;
+
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0]["user_id"], 42);
+    }
+
+    #[actix_rt::test]
+    async fn access_log_records_error_status() {
+        let logs = access_logs("/unknown").await;
+
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0]["status"], 404);
+    }
+}
@@ -1,15 +1,33 @@
+use std::rc::Rc;
 use std::sync::Arc;
 
 use actix_web::{
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
-    Error,
+    http::header,
+    web, Error, HttpMessage,
 };
 use futures_util::future::{ready, LocalBoxFuture, Ready};
+use serde::Deserialize;
 
 use crate::{
-    domains::auth_service::AuthService, repositories::auth_repository::AuthRepositoryImpl,
+    domains::auth_service::AuthService, errors::AppError,
+    repositories::auth_repository::AuthRepositoryImpl,
 };
 
+// ブラウザの WebSocket API はヘッダーを付けられないため、接続時のみクエリでトークンを受け付ける
+#[derive(Deserialize)]
+struct WebSocketTokenQuery {
+    access_token: String,
+}
+
+fn is_websocket_upgrade(req: &ServiceRequest) -> bool {
+    req.headers()
+        .get(header::UPGRADE)
+        .and_then(|h| h.to_str().ok())
+        .map(|s| s.eq_ignore_ascii_case("websocket"))
+        .unwrap_or(false)
+}
+
 pub struct AuthMiddleware {
     auth_service: Arc<AuthService<AuthRepositoryImpl>>,
 }
@@ -34,14 +52,14 @@ where
 
     fn new_transform(&self, service: S) -> Self::Future {
         ready(Ok(AuthMiddlewareMiddleware {
-            service,
+            service: Rc::new(service),
             auth_service: self.auth_service.clone(),
         }))
     }
 }
 
 pub struct AuthMiddlewareMiddleware<S> {
-    service: S,
+    service: Rc<S>,
     auth_service: Arc<AuthService<AuthRepositoryImpl>>,
 }
 
@@ -61,24 +79,32 @@ where
             .headers()
             .get("Authorization")
             .and_then(|h| h.to_str().ok())
-            .map(|s| s.to_string());
-        let auth_header = Arc::new(auth_header);
+            .map(|s| s.to_string())
+            .or_else(|| {
+                if !is_websocket_upgrade(&req) {
+                    return None;
+                }
+                web::Query::<WebSocketTokenQuery>::from_query(req.query_string())
+                    .ok()
+                    .map(|query| query.into_inner().access_token)
+            });
 
         let auth_service = self.auth_service.clone();
-        let fut = self.service.call(req);
+        let service = self.service.clone();
 
         Box::pin(async move {
-            let is_valid_token = match &*auth_header {
-                Some(token) => auth_service.validate_session(token).await.is_ok(),
-                None => false,
+            let session = match &auth_header {
+                Some(token) => auth_service.find_valid_session(token).await.ok(),
+                None => None,
             };
 
-            if is_valid_token {
-                fut.await
-            } else {
-                Err(actix_web::error::ErrorUnauthorized(
-                    "Invalid or missing token",
-                ))
+            match session {
+                Some(session) => {
+                    // ハンドラから web::ReqData<Session> で参照できるようにする
+                    req.extensions_mut().insert(session);
+                    service.call(req).await
+                }
+                None => Err(AppError::Unauthorized.into()),
             }
         })
     }
@@ -0,0 +1,114 @@
+use std::time::Instant;
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error,
+};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+
+use crate::infrastructure::metrics::metrics;
+
+// ルートに一致しなかったリクエストをまとめるラベル (パスをそのまま使うとラベルが際限なく増える)
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+pub struct MetricsMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for MetricsMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = MetricsMiddlewareMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(MetricsMiddlewareMiddleware { service }))
+    }
+}
+
+pub struct MetricsMiddlewareMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for MetricsMiddlewareMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let started_at = Instant::now();
+        let method = req.method().to_string();
+        let route = req
+            .match_pattern()
+            .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let result = fut.await;
+            let status = match &result {
+                Ok(res) => res.status(),
+                Err(err) => err.as_response_error().status_code(),
+            };
+            metrics().observe_http_request(&method, &route, status.as_u16(), started_at.elapsed());
+
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, web, App, HttpResponse};
+
+    use super::*;
+
+    #[actix_rt::test]
+    async fn requests_are_labeled_by_route_pattern() {
+        let app = test::init_service(
+            App::new().wrap(MetricsMiddleware).service(
+                web::resource("/metrics_middleware_test/{id}")
+                    .route(web::get().to(HttpResponse::NoContent)),
+            ),
+        )
+        .await;
+
+        for id in [1, 2] {
+            let req = test::TestRequest::get()
+                .uri(&format!("/metrics_middleware_test/{}", id))
+                .to_request();
+            test::call_service(&app, req).await;
+        }
+
+        let text = metrics().encode().unwrap();
+        assert!(text.contains(
+            r#"http_requests_total{method="GET",route="/metrics_middleware_test/{id}",status="204"} 2"#
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn unmatched_requests_share_one_label() {
+        let app = test::init_service(App::new().wrap(MetricsMiddleware)).await;
+
+        let req = test::TestRequest::delete()
+            .uri("/metrics_middleware_test/unknown/path")
+            .to_request();
+        test::call_service(&app, req).await;
+
+        let text = metrics().encode().unwrap();
+        assert!(
+            text.contains(r#"http_requests_total{method="DELETE",route="unmatched",status="404"}"#)
+        );
+        assert!(!text.contains("/metrics_middleware_test/unknown/path"));
+    }
+}
@@ -1 +1,5 @@
+pub mod access_log_middleware;
 pub mod auth_middleware;
+pub mod metrics_middleware;
+pub mod rate_limit_middleware;
+pub mod request_id_middleware;
@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::StatusCode,
+    Error,
+};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+
+use crate::{errors::AppError, utils::client_ip};
+
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy)]
+struct Window {
+    started_at: Instant,
+    count: u32,
+}
+
+// クライアント IP ごとに固定ウィンドウでリクエスト数を数える
+#[derive(Debug)]
+pub struct RateLimiter {
+    max_requests: u32,
+    window: Duration,
+    windows: Mutex<HashMap<String, Window>>,
+}
+
+impl RateLimiter {
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        RateLimiter {
+            max_requests,
+            window,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn acquire(&self, key: &str) -> Result<(), AppError> {
+        self.check(key)?;
+        self.record(key);
+        Ok(())
+    }
+
+    // 上限に達していれば TooManyRequests を返す (回数は数えない)
+    fn check(&self, key: &str) -> Result<(), AppError> {
+        let now = Instant::now();
+        let windows = self.windows.lock().unwrap();
+
+        match windows.get(key) {
+            Some(current)
+                if now - current.started_at < self.window && current.count >= self.max_requests =>
+            {
+                let retry_after = self.window - (now - current.started_at);
+                Err(AppError::TooManyRequests {
+                    retry_after_secs: retry_after.as_secs().max(1),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn record(&self, key: &str) {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+
+        if windows.len() >= MAX_TRACKED_CLIENTS {
+            let window = self.window;
+            windows.retain(|_, w| now - w.started_at < window);
+        }
+
+        let current = windows.entry(key.to_string()).or_insert(Window {
+            started_at: now,
+            count: 0,
+        });
+        if now - current.started_at >= self.window {
+            current.started_at = now;
+            current.count = 0;
+        }
+        current.count += 1;
+    }
+}
+
+// どのリクエストを上限の対象として数えるか
+#[derive(Debug, Clone, Copy)]
+enum Counting {
+    AllRequests,
+    // 認証に失敗した (401 を返した) リクエストだけを数える
+    UnauthorizedResponses,
+}
+
+pub struct RateLimitMiddleware {
+    limiter: Arc<RateLimiter>,
+    counting: Counting,
+}
+
+impl RateLimitMiddleware {
+    pub fn new(limiter: Arc<RateLimiter>) -> Self {
+        RateLimitMiddleware {
+            limiter,
+            counting: Counting::AllRequests,
+        }
+    }
+
+    // ログインのように、正常な利用者が短時間に繰り返し成功するエンドポイント向け
+    pub fn failures_only(limiter: Arc<RateLimiter>) -> Self {
+        RateLimitMiddleware {
+            limiter,
+            counting: Counting::UnauthorizedResponses,
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimitMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RateLimitMiddlewareMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddlewareMiddleware {
+            service,
+            limiter: self.limiter.clone(),
+            counting: self.counting,
+        }))
+    }
+}
+
+pub struct RateLimitMiddlewareMiddleware<S> {
+    service: S,
+    limiter: Arc<RateLimiter>,
+    counting: Counting,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddlewareMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let key = client_ip(req.request()).unwrap_or_default();
+
+        let limited = match self.counting {
+            Counting::AllRequests => self.limiter.acquire(&key),
+            Counting::UnauthorizedResponses => self.limiter.check(&key),
+        };
+        if let Err(err) = limited {
+            return Box::pin(async move { Err(err.into()) });
+        }
+
+        let fut = self.service.call(req);
+        match self.counting {
+            Counting::AllRequests => Box::pin(fut),
+            Counting::UnauthorizedResponses => {
+                let limiter = self.limiter.clone();
+                Box::pin(async move {
+                    let res = fut.await?;
+                    if res.status() == StatusCode::UNAUTHORIZED {
+                        limiter.record(&key);
+                    }
+                    Ok(res)
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use actix_web::{test, web, App, HttpResponse};
+
+    use super::*;
+    use crate::config::AppConfig;
+
+    const CLIENT_ADDR: &str = "192.0.2.1:40000";
+
+    fn login_request() -> test::TestRequest {
+        test::TestRequest::post()
+            .uri("/login")
+            .peer_addr(CLIENT_ADDR.parse::<SocketAddr>().unwrap())
+    }
+
+    // ミドルウェアが返したエラーもレスポンスのステータスに変換する
+    async fn status<S, R>(app: &S, req: R) -> StatusCode
+    where
+        S: Service<R, Response = ServiceResponse, Error = Error>,
+    {
+        match test::try_call_service(app, req).await {
+            Ok(res) => res.status(),
+            Err(err) => err.as_response_error().status_code(),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn default_login_limit_allows_benchmark_login_rate() {
+        // ベンチマーカーは 1 つの IP から 30 秒未満に 100 回ログインに成功する
+        let config = AppConfig::with_defaults().rate_limit;
+        let limiter = Arc::new(RateLimiter::new(config.login_max_requests, config.window()));
+        let app = test::init_service(
+            App::new().service(
+                web::resource("/login")
+                    .wrap(RateLimitMiddleware::failures_only(limiter))
+                    .route(web::post().to(HttpResponse::Ok)),
+            ),
+        )
+        .await;
+
+        for _ in 0..100 {
+            assert_eq!(
+                status(&app, login_request().to_request()).await,
+                StatusCode::OK
+            );
+        }
+    }
+
+    #[actix_rt::test]
+    async fn default_login_limit_rejects_repeated_failures() {
+        let config = AppConfig::with_defaults().rate_limit;
+        let limiter = Arc::new(RateLimiter::new(config.login_max_requests, config.window()));
+        let app = test::init_service(
+            App::new().service(
+                web::resource("/login")
+                    .wrap(RateLimitMiddleware::failures_only(limiter))
+                    .route(web::post().to(HttpResponse::Unauthorized)),
+            ),
+        )
+        .await;
+
+        for _ in 0..config.login_max_requests {
+            assert_eq!(
+                status(&app, login_request().to_request()).await,
+                StatusCode::UNAUTHORIZED
+            );
+        }
+        assert_eq!(
+            status(&app, login_request().to_request()).await,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    #[actix_rt::test]
+    async fn limit_returns_429_with_retry_after() {
+        let limiter = Arc::new(RateLimiter::new(2, Duration::from_secs(60)));
+        let app = test::init_service(
+            App::new().service(
+                web::resource("/register")
+                    .wrap(RateLimitMiddleware::new(limiter))
+                    .route(web::post().to(HttpResponse::Created)),
+            ),
+        )
+        .await;
+        let register_request = || {
+            test::TestRequest::post()
+                .uri("/register")
+                .peer_addr(CLIENT_ADDR.parse::<SocketAddr>().unwrap())
+                .to_request()
+        };
+
+        for _ in 0..2 {
+            assert_eq!(status(&app, register_request()).await, StatusCode::CREATED);
+        }
+        let err = test::try_call_service(&app, register_request())
+            .await
+            .unwrap_err();
+        let res = err.error_response();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after: u64 = res
+            .headers()
+            .get("Retry-After")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((1..=60).contains(&retry_after));
+
+        // 別の IP は制限されない
+        let other = test::TestRequest::post()
+            .uri("/register")
+            .peer_addr("192.0.2.2:40000".parse::<SocketAddr>().unwrap())
+            .to_request();
+        assert_eq!(status(&app, other).await, StatusCode::CREATED);
+    }
+
+    #[actix_rt::test]
+    async fn limit_resets_when_window_rolls_over() {
+        let limiter = RateLimiter::new(1, Duration::from_millis(100));
+
+        assert!(limiter.acquire("client").is_ok());
+        assert_eq!(
+            limiter.acquire("client").unwrap_err().code(),
+            "too_many_requests"
+        );
+
+        actix_rt::time::sleep(Duration::from_millis(120)).await;
+
+        assert!(limiter.acquire("client").is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn check_does_not_count_requests() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(60));
+
+        for _ in 0..3 {
+            assert!(limiter.check("client").is_ok());
+        }
+        limiter.record("client");
+        assert!(limiter.check("client").is_err());
+    }
+}
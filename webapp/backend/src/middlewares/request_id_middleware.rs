@@ -0,0 +1,201 @@
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    error::InternalError,
+    http::header::{HeaderMap, HeaderName, HeaderValue},
+    Error,
+};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+// 処理中のリクエストの ID を返す（リクエストのスコープ外では None）
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|request_id| request_id.clone()).ok()
+}
+
+fn is_valid_request_id(request_id: &str) -> bool {
+    (1..=128).contains(&request_id.len())
+        && request_id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'.')
+}
+
+fn generate_request_id() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+pub struct RequestIdMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestIdMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestIdMiddlewareMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestIdMiddlewareMiddleware { service }))
+    }
+}
+
+pub struct RequestIdMiddlewareMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdMiddlewareMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        // 上流（nginx 等）から渡された ID があれば引き継ぐ
+        let request_id = req
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|h| h.to_str().ok())
+            .filter(|id| is_valid_request_id(id))
+            .map(|id| id.to_string())
+            .unwrap_or_else(generate_request_id);
+
+        // ルーティングで HttpRequest を書き換えるため、内側のサービスの呼び出し中に
+        // リクエストの参照を保持してはいけない
+        let fut = self.service.call(req);
+
+        Box::pin(REQUEST_ID.scope(request_id.clone(), async move {
+            match fut.await {
+                Ok(mut res) => {
+                    insert_request_id(res.headers_mut(), &request_id);
+                    Ok(res)
+                }
+                // ミドルウェアで発生したエラーも、リクエスト ID を参照できるスコープ内でレスポンスを組み立てておく
+                Err(err) => {
+                    let mut response = err.error_response();
+                    insert_request_id(response.headers_mut(), &request_id);
+                    Err(InternalError::from_response(err, response).into())
+                }
+            }
+        }))
+    }
+}
+
+fn insert_request_id(headers: &mut HeaderMap, request_id: &str) {
+    if let Ok(value) = HeaderValue::from_str(request_id) {
+        headers.insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        body::to_bytes, dev::Service as _, middleware::from_fn, test, web, App, HttpResponse,
+    };
+    use serde_json::Value;
+
+    use super::*;
+    use crate::errors::AppError;
+
+    async fn echo_request_id(path: web::Path<i32>) -> HttpResponse {
+        HttpResponse::Ok().body(format!(
+            "{}:{}",
+            path.into_inner(),
+            current_request_id().unwrap_or_default()
+        ))
+    }
+
+    #[actix_rt::test]
+    async fn routed_request_gets_generated_request_id() {
+        let app = test::init_service(
+            App::new()
+                .wrap(RequestIdMiddleware)
+                .route("/api/x/{id}", web::get().to(echo_request_id)),
+        )
+        .await;
+
+        let res =
+            test::call_service(&app, test::TestRequest::get().uri("/api/x/7").to_request()).await;
+
+        assert!(res.status().is_success());
+        let request_id = res
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(request_id.len(), 32);
+        let body = test::read_body(res).await;
+        assert_eq!(body, format!("7:{}", request_id));
+    }
+
+    #[actix_rt::test]
+    async fn valid_incoming_request_id_is_kept_and_invalid_one_replaced() {
+        let app = test::init_service(
+            App::new()
+                .wrap(RequestIdMiddleware)
+                .route("/api/x/{id}", web::get().to(echo_request_id)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/x/1")
+            .insert_header((REQUEST_ID_HEADER, "from-nginx.1"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(
+            res.headers().get(REQUEST_ID_HEADER).unwrap(),
+            "from-nginx.1"
+        );
+
+        let req = test::TestRequest::get()
+            .uri("/api/x/1")
+            .insert_header((REQUEST_ID_HEADER, "bad id"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_ne!(res.headers().get(REQUEST_ID_HEADER).unwrap(), "bad id");
+    }
+
+    #[actix_rt::test]
+    async fn middleware_errors_carry_request_id() {
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(
+                    |_req, _next: actix_web::middleware::Next<_>| async {
+                        Err::<ServiceResponse, _>(AppError::Unauthorized.into())
+                    },
+                ))
+                .wrap(RequestIdMiddleware)
+                .route("/api/x/{id}", web::get().to(echo_request_id)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/x/1")
+            .insert_header((REQUEST_ID_HEADER, "abc"))
+            .to_request();
+        let err = app.call(req).await.unwrap_err();
+        let res = err.error_response();
+
+        assert_eq!(res.status(), 401);
+        assert_eq!(res.headers().get(REQUEST_ID_HEADER).unwrap(), "abc");
+        let body: Value =
+            serde_json::from_slice(&to_bytes(res.into_body()).await.unwrap()).unwrap();
+        assert_eq!(body["code"], "unauthorized");
+        assert_eq!(body["request_id"], "abc");
+    }
+}
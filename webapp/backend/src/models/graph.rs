@@ -17,6 +17,15 @@ pub struct Edge {
     pub weight: i32,
 }
 
+// 辺の重みと通行止めの変更 (None の項目は変更しない)
+#[derive(Clone, Debug)]
+pub struct EdgeUpdate {
+    pub node_a_id: i32,
+    pub node_b_id: i32,
+    pub weight: Option<i32>,
+    pub is_closed: Option<bool>,
+}
+
 #[derive(Debug)]
 pub struct Graph {
     pub nodes: HashMap<i32, Node>,
@@ -72,18 +81,51 @@ impl Graph {
             .push(reverse_edge);
     }
 
-    pub fn shortest_path(&self, from_node_id: i32, to_node_id: i32) -> i32 {
+    // 最短経路のコストと、探索中にヒープから取り出したノード数を返す
+    pub fn shortest_path_with_stats(&self, from_node_id: i32, to_node_id: i32) -> (i32, usize) {
+        let (cost, visited_nodes) = self.dijkstra(from_node_id, to_node_id, |_, _| {});
+
+        // 目的地に到達できない場合は i32::MAX を返す
+        (cost.unwrap_or(i32::MAX), visited_nodes)
+    }
+
+    // 最短経路のコストと、出発ノードから目的ノードまでに通るノードを順に返す
+    // 目的地に到達できない場合は None を返す
+    pub fn shortest_route(&self, from_node_id: i32, to_node_id: i32) -> Option<(i32, Vec<i32>)> {
+        let mut previous: HashMap<i32, i32> = HashMap::new();
+        let (cost, _) = self.dijkstra(from_node_id, to_node_id, |node, prev| {
+            previous.insert(node, prev);
+        });
+        let cost = cost?;
+
+        // 目的地から出発地まで辿ってから反転する
+        let mut route = vec![to_node_id];
+        let mut current = to_node_id;
+        while let Some(&prev) = previous.get(&current) {
+            route.push(prev);
+            current = prev;
+        }
+        route.reverse();
+        Some((cost, route))
+    }
+
+    // 目的地までのコストと、探索中にヒープから取り出したノード数を返す
+    // 距離を更新するたびに on_relax(更新したノード, 直前のノード) を呼ぶ
+    fn dijkstra<F: FnMut(i32, i32)>(&self, from_node_id: i32, to_node_id: i32, mut on_relax: F) -> (Option<i32>, usize) {
         let mut dist: HashMap<i32, i32> = HashMap::new();
         let mut heap = BinaryHeap::new();
+        let mut visited_nodes = 0;
 
         // 開始ノードをヒープに追加
         heap.push(State { cost: 0, node: from_node_id });
         dist.insert(from_node_id, 0);
 
         while let Some(State { cost, node }) = heap.pop() {
+            visited_nodes += 1;
+
             // 目的地に到達した場合、コストを返す
             if node == to_node_id {
-                return cost;
+                return (Some(cost), visited_nodes);
             }
 
             // より高コストの経路を見つけた場合はスキップ
@@ -102,13 +144,66 @@ impl Graph {
                     // より短い経路が見つかった場合、距離を更新してヒープに追加
                     if next.cost < *dist.get(&next.node).unwrap_or(&i32::MAX) {
                         dist.insert(next.node, next.cost);
+                        on_relax(next.node, node);
                         heap.push(next);
                     }
                 }
             }
         }
 
-        // 目的地に到達できない場合は i32::MAX を返す
-        i32::MAX
+        (None, visited_nodes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1 - 2 の直通より 1 - 3 - 2 の迂回の方が短いグラフ
+    fn graph() -> Graph {
+        let mut graph = Graph::new();
+        for id in 1..=4 {
+            graph.add_node(Node { id, x: 0, y: 0 });
+        }
+        for (node_a_id, node_b_id, weight) in [(1, 2, 10), (1, 3, 2), (3, 2, 3)] {
+            graph.add_edge(Edge {
+                node_a_id,
+                node_b_id,
+                weight,
+            });
+        }
+        graph
+    }
+
+    #[test]
+    fn shortest_route_follows_cheapest_path() {
+        let graph = graph();
+
+        assert_eq!(graph.shortest_route(1, 2), Some((5, vec![1, 3, 2])));
+        // 辺は双方向に辿れる
+        assert_eq!(graph.shortest_route(2, 1), Some((5, vec![2, 3, 1])));
+    }
+
+    #[test]
+    fn shortest_route_to_same_node_is_empty_trip() {
+        assert_eq!(graph().shortest_route(1, 1), Some((0, vec![1])));
+    }
+
+    #[test]
+    fn unreachable_node_has_no_route() {
+        let graph = graph();
+
+        assert_eq!(graph.shortest_route(1, 4), None);
+        assert_eq!(graph.shortest_path_with_stats(1, 4).0, i32::MAX);
+    }
+
+    #[test]
+    fn shortest_path_cost_matches_route() {
+        let graph = graph();
+
+        let (distance, visited_nodes) = graph.shortest_path_with_stats(1, 2);
+
+        assert_eq!(distance, 5);
+        assert!(visited_nodes >= 3);
     }
 }
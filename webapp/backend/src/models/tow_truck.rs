@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use sqlx::FromRow;
 
 #[derive(FromRow, Clone, Debug)]
@@ -9,3 +10,25 @@ pub struct TowTruck {
     pub area_id: i32,
     pub node_id: i32,
 }
+
+#[derive(FromRow, Clone, Debug)]
+pub struct DriverShift {
+    pub id: i32,
+    pub tow_truck_id: i32,
+    pub driver_id: i32,
+    pub area_id: i32,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+// 勤務中のドライバーと、そのレッカー車の状態
+#[derive(FromRow, Clone, Debug)]
+pub struct OnShiftDriver {
+    pub shift_id: i32,
+    pub tow_truck_id: i32,
+    pub driver_id: i32,
+    pub driver_username: String,
+    pub status: String,
+    pub area_id: i32,
+    pub started_at: DateTime<Utc>,
+}
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use sqlx::FromRow;
 
 #[derive(FromRow, Clone, Debug)]
@@ -15,6 +16,7 @@ pub struct Session {
     pub user_id: i32,
     pub session_token: String,
     pub is_valid: bool,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(FromRow, Clone, Debug)]
@@ -23,3 +25,11 @@ pub struct Dispatcher {
     pub user_id: i32,
     pub area_id: i32,
 }
+
+#[derive(FromRow, Clone, Debug)]
+pub struct PasswordResetToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
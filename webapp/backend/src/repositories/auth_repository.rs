@@ -1,7 +1,9 @@
 use crate::errors::AppError;
-use crate::models::user::{Dispatcher, User};
+use crate::models::user::{Dispatcher, PasswordResetToken, User};
 use crate::{domains::auth_service::AuthRepository, models::user::Session};
+use chrono::{DateTime, Utc};
 use sqlx::mysql::MySqlPool;
+use tracing::instrument;
 
 #[derive(Debug)]
 pub struct AuthRepositoryImpl {
@@ -15,6 +17,7 @@ impl AuthRepositoryImpl {
 }
 
 impl AuthRepository for AuthRepositoryImpl {
+    #[instrument(level = "debug", skip_all)]
     async fn find_user_by_id(&self, id: i32) -> Result<Option<User>, AppError> {
         let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
             .bind(id)
@@ -24,6 +27,7 @@ impl AuthRepository for AuthRepositoryImpl {
         Ok(user)
     }
 
+    #[instrument(level = "debug", skip_all)]
     async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, AppError> {
         let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE username = ?")
             .bind(username)
@@ -33,6 +37,7 @@ impl AuthRepository for AuthRepositoryImpl {
         Ok(user)
     }
 
+    #[instrument(level = "debug", skip_all)]
     async fn find_profile_image_name_by_user_id(
         &self,
         user_id: i32,
@@ -45,6 +50,22 @@ impl AuthRepository for AuthRepositoryImpl {
         Ok(profile_image_name)
     }
 
+    #[instrument(level = "debug", skip_all)]
+    async fn update_profile_image_name(
+        &self,
+        user_id: i32,
+        profile_image_name: &str,
+    ) -> Result<(), AppError> {
+        sqlx::query("UPDATE users SET profile_image = ? WHERE id = ?")
+            .bind(profile_image_name)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
     async fn create_user(
         &self,
         username: &str,
@@ -61,25 +82,30 @@ impl AuthRepository for AuthRepositoryImpl {
         Ok(())
     }
 
+    #[instrument(level = "debug", skip_all)]
     async fn create_session(&self, user_id: i32, session_token: &str) -> Result<(), AppError> {
-        sqlx::query("INSERT INTO sessions (user_id, session_token) VALUES (?, ?)")
+        // 期限切れの判定は UTC で行うため、DB の CURRENT_TIMESTAMP (サーバーのタイムゾーン) に頼らず UTC で記録する
+        sqlx::query("INSERT INTO sessions (user_id, session_token, created_at) VALUES (?, ?, ?)")
             .bind(user_id)
             .bind(session_token)
+            .bind(Utc::now())
             .execute(&self.pool)
             .await?;
 
         Ok(())
     }
 
+    #[instrument(level = "debug", skip_all)]
     async fn delete_session(&self, session_token: &str) -> Result<(), AppError> {
         sqlx::query("UPDATE sessions SET is_valid = false WHERE session_token = ?")
             .bind(session_token)
             .execute(&self.pool)
             .await?;
-    
+
         Ok(())
-    }    
+    }
 
+    #[instrument(level = "debug", skip_all)]
     async fn find_session_by_session_token(
         &self,
         session_token: &str,
@@ -93,6 +119,7 @@ impl AuthRepository for AuthRepositoryImpl {
         Ok(session)
     }
 
+    #[instrument(level = "debug", skip_all)]
     async fn find_dispatcher_by_id(&self, id: i32) -> Result<Option<Dispatcher>, AppError> {
         let dispatcher = sqlx::query_as::<_, Dispatcher>("SELECT * FROM dispatchers WHERE id = ?")
             .bind(id)
@@ -102,6 +129,7 @@ impl AuthRepository for AuthRepositoryImpl {
         Ok(dispatcher)
     }
 
+    #[instrument(level = "debug", skip_all)]
     async fn find_dispatcher_by_user_id(
         &self,
         user_id: i32,
@@ -115,6 +143,7 @@ impl AuthRepository for AuthRepositoryImpl {
         Ok(dispatcher)
     }
 
+    #[instrument(level = "debug", skip_all)]
     async fn create_dispatcher(&self, user_id: i32, area_id: i32) -> Result<(), AppError> {
         sqlx::query("INSERT INTO dispatchers (user_id, area_id) VALUES (?, ?)")
             .bind(user_id)
@@ -124,4 +153,121 @@ impl AuthRepository for AuthRepositoryImpl {
 
         Ok(())
     }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn update_password(&self, user_id: i32, password: &str) -> Result<(), AppError> {
+        sqlx::query("UPDATE users SET password = ? WHERE id = ?")
+            .bind(password)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn update_role(&self, user_id: i32, role: &str) -> Result<(), AppError> {
+        sqlx::query("UPDATE users SET role = ? WHERE id = ?")
+            .bind(role)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn invalidate_sessions_by_user_id(
+        &self,
+        user_id: i32,
+        except_session_token: Option<&str>,
+    ) -> Result<(), AppError> {
+        match except_session_token {
+            Some(session_token) => {
+                sqlx::query(
+                    "UPDATE sessions SET is_valid = false WHERE user_id = ? AND session_token <> ?",
+                )
+                .bind(user_id)
+                .bind(session_token)
+                .execute(&self.pool)
+                .await?;
+            }
+            None => {
+                sqlx::query("UPDATE sessions SET is_valid = false WHERE user_id = ?")
+                    .bind(user_id)
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn create_password_reset_token(
+        &self,
+        user_id: i32,
+        token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO password_reset_tokens (user_id, token, expires_at) VALUES (?, ?, ?)",
+        )
+        .bind(user_id)
+        .bind(token)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_password_reset_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<PasswordResetToken>, AppError> {
+        let password_reset_token = sqlx::query_as::<_, PasswordResetToken>(
+            "SELECT * FROM password_reset_tokens WHERE token = ?",
+        )
+        .bind(token)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(password_reset_token)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn mark_password_reset_token_used(&self, id: i32) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            "UPDATE password_reset_tokens SET used_at = UTC_TIMESTAMP() WHERE id = ? AND used_at IS NULL",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_stale_sessions(
+        &self,
+        created_before: Option<DateTime<Utc>>,
+    ) -> Result<u64, AppError> {
+        let result = match created_before {
+            Some(created_before) => {
+                sqlx::query("DELETE FROM sessions WHERE is_valid = false OR created_at < ?")
+                    .bind(created_before)
+                    .execute(&self.pool)
+                    .await?
+            }
+            None => {
+                sqlx::query("DELETE FROM sessions WHERE is_valid = false")
+                    .execute(&self.pool)
+                    .await?
+            }
+        };
+
+        Ok(result.rows_affected())
+    }
 }
@@ -0,0 +1,797 @@
+// サービスのテストで DB の代わりに使うリポジトリ
+// 状態は Arc で共有するので、clone したリポジトリとテスト側から同じ内容を参照できる
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+
+use crate::domains::auth_service::AuthRepository;
+use crate::domains::map_service::MapRepository;
+use crate::domains::order_service::OrderRepository;
+use crate::domains::tow_truck_service::TowTruckRepository;
+use crate::errors::AppError;
+use crate::models::graph::{Edge, EdgeUpdate, Node};
+use crate::models::order::Order;
+use crate::models::tow_truck::{DriverShift, OnShiftDriver, TowTruck};
+use crate::models::user::{Dispatcher, PasswordResetToken, Session, User};
+
+fn row_not_found() -> AppError {
+    AppError::SqlxError(sqlx::Error::RowNotFound)
+}
+
+fn next_id(ids: impl Iterator<Item = i32>) -> i32 {
+    ids.max().unwrap_or(0) + 1
+}
+
+// page_size が負の場合は全件を返す
+fn paginate<T>(items: Vec<T>, page: i32, page_size: i32) -> Vec<T> {
+    if page_size < 0 {
+        return items;
+    }
+    items
+        .into_iter()
+        .skip((page * page_size) as usize)
+        .take(page_size as usize)
+        .collect()
+}
+
+#[derive(Debug, Default)]
+pub struct AuthState {
+    pub users: Vec<User>,
+    pub dispatchers: Vec<Dispatcher>,
+    pub sessions: Vec<Session>,
+    pub password_reset_tokens: Vec<(String, PasswordResetToken)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryAuthRepository {
+    pub state: Arc<Mutex<AuthState>>,
+}
+
+impl InMemoryAuthRepository {
+    pub fn new(users: Vec<User>, dispatchers: Vec<Dispatcher>) -> Self {
+        InMemoryAuthRepository {
+            state: Arc::new(Mutex::new(AuthState {
+                users,
+                dispatchers,
+                ..AuthState::default()
+            })),
+        }
+    }
+}
+
+impl AuthRepository for InMemoryAuthRepository {
+    async fn create_user(
+        &self,
+        username: &str,
+        password: &str,
+        role: &str,
+    ) -> Result<(), AppError> {
+        let mut state = self.state.lock().unwrap();
+        let id = next_id(state.users.iter().map(|u| u.id));
+        state.users.push(User {
+            id,
+            username: username.to_string(),
+            password: password.to_string(),
+            profile_image: "default.png".to_string(),
+            role: role.to_string(),
+        });
+        Ok(())
+    }
+
+    async fn find_user_by_id(&self, id: i32) -> Result<Option<User>, AppError> {
+        let state = self.state.lock().unwrap();
+        Ok(state.users.iter().find(|u| u.id == id).cloned())
+    }
+
+    async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, AppError> {
+        let state = self.state.lock().unwrap();
+        Ok(state.users.iter().find(|u| u.username == username).cloned())
+    }
+
+    async fn create_dispatcher(&self, user_id: i32, area_id: i32) -> Result<(), AppError> {
+        let mut state = self.state.lock().unwrap();
+        let id = next_id(state.dispatchers.iter().map(|d| d.id));
+        state.dispatchers.push(Dispatcher {
+            id,
+            user_id,
+            area_id,
+        });
+        Ok(())
+    }
+
+    async fn find_dispatcher_by_id(&self, id: i32) -> Result<Option<Dispatcher>, AppError> {
+        let state = self.state.lock().unwrap();
+        Ok(state.dispatchers.iter().find(|d| d.id == id).cloned())
+    }
+
+    async fn find_dispatcher_by_user_id(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<Dispatcher>, AppError> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .dispatchers
+            .iter()
+            .find(|d| d.user_id == user_id)
+            .cloned())
+    }
+
+    async fn find_profile_image_name_by_user_id(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<String>, AppError> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .users
+            .iter()
+            .find(|u| u.id == user_id)
+            .map(|u| u.profile_image.clone()))
+    }
+
+    async fn update_profile_image_name(
+        &self,
+        user_id: i32,
+        profile_image_name: &str,
+    ) -> Result<(), AppError> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(user) = state.users.iter_mut().find(|u| u.id == user_id) {
+            user.profile_image = profile_image_name.to_string();
+        }
+        Ok(())
+    }
+
+    async fn create_session(&self, user_id: i32, session_token: &str) -> Result<(), AppError> {
+        let mut state = self.state.lock().unwrap();
+        let id = next_id(state.sessions.iter().map(|s| s.id));
+        state.sessions.push(Session {
+            id,
+            user_id,
+            session_token: session_token.to_string(),
+            is_valid: true,
+            created_at: Utc::now(),
+        });
+        Ok(())
+    }
+
+    async fn delete_session(&self, session_token: &str) -> Result<(), AppError> {
+        let mut state = self.state.lock().unwrap();
+        for session in state
+            .sessions
+            .iter_mut()
+            .filter(|s| s.session_token == session_token)
+        {
+            session.is_valid = false;
+        }
+        Ok(())
+    }
+
+    async fn find_session_by_session_token(
+        &self,
+        session_token: &str,
+    ) -> Result<Session, AppError> {
+        let state = self.state.lock().unwrap();
+        state
+            .sessions
+            .iter()
+            .find(|s| s.session_token == session_token)
+            .cloned()
+            .ok_or_else(row_not_found)
+    }
+
+    async fn update_password(&self, user_id: i32, password: &str) -> Result<(), AppError> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(user) = state.users.iter_mut().find(|u| u.id == user_id) {
+            user.password = password.to_string();
+        }
+        Ok(())
+    }
+
+    async fn update_role(&self, user_id: i32, role: &str) -> Result<(), AppError> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(user) = state.users.iter_mut().find(|u| u.id == user_id) {
+            user.role = role.to_string();
+        }
+        Ok(())
+    }
+
+    async fn invalidate_sessions_by_user_id(
+        &self,
+        user_id: i32,
+        except_session_token: Option<&str>,
+    ) -> Result<(), AppError> {
+        let mut state = self.state.lock().unwrap();
+        for session in state.sessions.iter_mut().filter(|s| {
+            s.user_id == user_id && Some(s.session_token.as_str()) != except_session_token
+        }) {
+            session.is_valid = false;
+        }
+        Ok(())
+    }
+
+    async fn create_password_reset_token(
+        &self,
+        user_id: i32,
+        token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let mut state = self.state.lock().unwrap();
+        let id = next_id(state.password_reset_tokens.iter().map(|(_, t)| t.id));
+        state.password_reset_tokens.push((
+            token.to_string(),
+            PasswordResetToken {
+                id,
+                user_id,
+                expires_at,
+                used_at: None,
+            },
+        ));
+        Ok(())
+    }
+
+    async fn find_password_reset_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<PasswordResetToken>, AppError> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .password_reset_tokens
+            .iter()
+            .find(|(t, _)| t == token)
+            .map(|(_, t)| t.clone()))
+    }
+
+    async fn mark_password_reset_token_used(&self, id: i32) -> Result<bool, AppError> {
+        let mut state = self.state.lock().unwrap();
+        match state
+            .password_reset_tokens
+            .iter_mut()
+            .find(|(_, t)| t.id == id && t.used_at.is_none())
+        {
+            Some((_, token)) => {
+                token.used_at = Some(Utc::now());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn delete_stale_sessions(
+        &self,
+        created_before: Option<DateTime<Utc>>,
+    ) -> Result<u64, AppError> {
+        let mut state = self.state.lock().unwrap();
+        let before = state.sessions.len();
+        state.sessions.retain(|s| {
+            s.is_valid && created_before.is_none_or(|created_before| s.created_at >= created_before)
+        });
+        Ok((before - state.sessions.len()) as u64)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct OrderState {
+    pub orders: Vec<Order>,
+    // (order_id, tow_truck_id, completed_time)
+    pub completed_orders: Vec<(i32, i32, DateTime<Utc>)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryOrderRepository {
+    pub state: Arc<Mutex<OrderState>>,
+}
+
+impl InMemoryOrderRepository {
+    pub fn new(orders: Vec<Order>) -> Self {
+        InMemoryOrderRepository {
+            state: Arc::new(Mutex::new(OrderState {
+                orders,
+                ..OrderState::default()
+            })),
+        }
+    }
+
+    pub fn order(&self, id: i32) -> Option<Order> {
+        let state = self.state.lock().unwrap();
+        state.orders.iter().find(|o| o.id == id).cloned()
+    }
+
+    fn update<F: FnOnce(&mut Order)>(&self, id: i32, f: F) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(order) = state.orders.iter_mut().find(|o| o.id == id) {
+            f(order);
+        }
+    }
+}
+
+impl OrderRepository for InMemoryOrderRepository {
+    async fn find_order_by_id(&self, id: i32) -> Result<Order, AppError> {
+        self.order(id).ok_or_else(row_not_found)
+    }
+
+    async fn update_order_status(&self, order_id: i32, status: &str) -> Result<(), AppError> {
+        self.update(order_id, |order| order.status = status.to_string());
+        Ok(())
+    }
+
+    // 並び替えとエリアでの絞り込みは行わない
+    async fn get_paginated_orders(
+        &self,
+        page: i32,
+        page_size: i32,
+        _: Option<String>,
+        _: Option<String>,
+        status: Option<String>,
+        _: Option<i32>,
+    ) -> Result<Vec<Order>, AppError> {
+        let state = self.state.lock().unwrap();
+        let orders = state
+            .orders
+            .iter()
+            .filter(|o| status.as_ref().is_none_or(|status| &o.status == status))
+            .cloned()
+            .collect();
+        Ok(paginate(orders, page, page_size))
+    }
+
+    async fn create_order(
+        &self,
+        customer_id: i32,
+        node_id: i32,
+        car_value: f64,
+    ) -> Result<i32, AppError> {
+        let mut state = self.state.lock().unwrap();
+        let id = next_id(state.orders.iter().map(|o| o.id));
+        state.orders.push(Order {
+            id,
+            client_id: customer_id,
+            dispatcher_id: None,
+            tow_truck_id: None,
+            status: "pending".to_string(),
+            node_id,
+            car_value,
+            order_time: Utc::now(),
+            completed_time: None,
+        });
+        Ok(id)
+    }
+
+    async fn update_order_dispatched(
+        &self,
+        id: i32,
+        dispatcher_id: i32,
+        tow_truck_id: i32,
+    ) -> Result<(), AppError> {
+        self.update(id, |order| {
+            order.dispatcher_id = Some(dispatcher_id);
+            order.tow_truck_id = Some(tow_truck_id);
+            order.status = "dispatched".to_string();
+        });
+        Ok(())
+    }
+
+    async fn get_orders_by_client_id(
+        &self,
+        client_id: i32,
+        statuses: &[String],
+        page: i32,
+        page_size: i32,
+    ) -> Result<Vec<Order>, AppError> {
+        let state = self.state.lock().unwrap();
+        let mut orders: Vec<Order> = state
+            .orders
+            .iter()
+            .filter(|o| o.client_id == client_id)
+            .filter(|o| statuses.is_empty() || statuses.contains(&o.status))
+            .cloned()
+            .collect();
+        orders.sort_by_key(|o| Reverse((o.order_time, o.id)));
+        Ok(paginate(orders, page, page_size))
+    }
+
+    async fn find_active_order_by_tow_truck_id(
+        &self,
+        tow_truck_id: i32,
+    ) -> Result<Option<Order>, AppError> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .orders
+            .iter()
+            .filter(|o| o.tow_truck_id == Some(tow_truck_id))
+            .filter(|o| ["dispatched", "arrived", "picked_up"].contains(&o.status.as_str()))
+            .max_by_key(|o| (o.order_time, o.id))
+            .cloned())
+    }
+
+    async fn get_orders_by_tow_truck_id(
+        &self,
+        tow_truck_id: i32,
+        page: i32,
+        page_size: i32,
+    ) -> Result<Vec<Order>, AppError> {
+        let state = self.state.lock().unwrap();
+        let mut orders: Vec<Order> = state
+            .orders
+            .iter()
+            .filter(|o| o.tow_truck_id == Some(tow_truck_id))
+            .cloned()
+            .collect();
+        orders.sort_by_key(|o| Reverse((o.order_time, o.id)));
+        Ok(paginate(orders, page, page_size))
+    }
+
+    async fn advance_order_status(
+        &self,
+        order_id: i32,
+        current_status: &str,
+        next_status: &str,
+    ) -> Result<bool, AppError> {
+        let mut state = self.state.lock().unwrap();
+        match state
+            .orders
+            .iter_mut()
+            .find(|o| o.id == order_id && o.status == current_status)
+        {
+            Some(order) => {
+                order.status = next_status.to_string();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn update_order_completed(
+        &self,
+        order_id: i32,
+        completed_time: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let mut state = self.state.lock().unwrap();
+        match state
+            .orders
+            .iter_mut()
+            .find(|o| o.id == order_id && o.status == "picked_up")
+        {
+            Some(order) => {
+                order.status = "completed".to_string();
+                order.completed_time = Some(completed_time);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn create_completed_order(
+        &self,
+        order_id: i32,
+        tow_truck_id: i32,
+        completed_time: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .completed_orders
+            .push((order_id, tow_truck_id, completed_time));
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct TowTruckState {
+    pub tow_trucks: Vec<TowTruck>,
+    pub shifts: Vec<DriverShift>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryTowTruckRepository {
+    pub state: Arc<Mutex<TowTruckState>>,
+}
+
+impl InMemoryTowTruckRepository {
+    pub fn new(tow_trucks: Vec<TowTruck>) -> Self {
+        InMemoryTowTruckRepository {
+            state: Arc::new(Mutex::new(TowTruckState {
+                tow_trucks,
+                ..TowTruckState::default()
+            })),
+        }
+    }
+
+    pub fn tow_truck(&self, id: i32) -> Option<TowTruck> {
+        let state = self.state.lock().unwrap();
+        state.tow_trucks.iter().find(|t| t.id == id).cloned()
+    }
+}
+
+impl TowTruckRepository for InMemoryTowTruckRepository {
+    async fn get_paginated_tow_trucks(
+        &self,
+        page: i32,
+        page_size: i32,
+        status: Option<String>,
+        area_id: Option<i32>,
+    ) -> Result<Vec<TowTruck>, AppError> {
+        let state = self.state.lock().unwrap();
+        let tow_trucks = state
+            .tow_trucks
+            .iter()
+            .filter(|t| status.as_ref().is_none_or(|status| &t.status == status))
+            .filter(|t| area_id.is_none_or(|area_id| t.area_id == area_id))
+            .cloned()
+            .collect();
+        Ok(paginate(tow_trucks, page, page_size))
+    }
+
+    async fn update_location(&self, truck_id: i32, node_id: i32) -> Result<(), AppError> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(tow_truck) = state.tow_trucks.iter_mut().find(|t| t.id == truck_id) {
+            tow_truck.node_id = node_id;
+        }
+        Ok(())
+    }
+
+    async fn update_status(&self, truck_id: i32, status: &str) -> Result<(), AppError> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(tow_truck) = state.tow_trucks.iter_mut().find(|t| t.id == truck_id) {
+            tow_truck.status = status.to_string();
+        }
+        Ok(())
+    }
+
+    async fn find_tow_truck_by_id(&self, id: i32) -> Result<Option<TowTruck>, AppError> {
+        Ok(self.tow_truck(id))
+    }
+
+    async fn find_tow_truck_by_driver_id(
+        &self,
+        driver_id: i32,
+    ) -> Result<Option<TowTruck>, AppError> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .tow_trucks
+            .iter()
+            .find(|t| t.driver_id == driver_id)
+            .cloned())
+    }
+
+    async fn start_shift(
+        &self,
+        tow_truck_id: i32,
+        driver_id: i32,
+        area_id: i32,
+        started_at: DateTime<Utc>,
+    ) -> Result<i32, AppError> {
+        let mut state = self.state.lock().unwrap();
+        // DB では勤務中のシフトに対する一意制約で弾かれる
+        if state
+            .shifts
+            .iter()
+            .any(|s| s.tow_truck_id == tow_truck_id && s.ended_at.is_none())
+        {
+            return Err(AppError::Conflict);
+        }
+
+        let id = next_id(state.shifts.iter().map(|s| s.id));
+        state.shifts.push(DriverShift {
+            id,
+            tow_truck_id,
+            driver_id,
+            area_id,
+            started_at,
+            ended_at: None,
+        });
+        if let Some(tow_truck) = state
+            .tow_trucks
+            .iter_mut()
+            .find(|t| t.id == tow_truck_id && t.status == "off_duty")
+        {
+            tow_truck.status = "available".to_string();
+        }
+        Ok(id)
+    }
+
+    async fn end_shift(
+        &self,
+        shift_id: i32,
+        tow_truck_id: i32,
+        ended_at: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let mut state = self.state.lock().unwrap();
+        let shift_index = state
+            .shifts
+            .iter()
+            .position(|s| s.id == shift_id && s.ended_at.is_none());
+        let tow_truck_index = state
+            .tow_trucks
+            .iter()
+            .position(|t| t.id == tow_truck_id && t.status != "busy");
+
+        match (shift_index, tow_truck_index) {
+            (Some(shift_index), Some(tow_truck_index)) => {
+                state.shifts[shift_index].ended_at = Some(ended_at);
+                state.tow_trucks[tow_truck_index].status = "off_duty".to_string();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn find_open_shift_by_driver_id(
+        &self,
+        driver_id: i32,
+    ) -> Result<Option<DriverShift>, AppError> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .shifts
+            .iter()
+            .find(|s| s.driver_id == driver_id && s.ended_at.is_none())
+            .cloned())
+    }
+
+    async fn find_shift_by_id(&self, id: i32) -> Result<Option<DriverShift>, AppError> {
+        let state = self.state.lock().unwrap();
+        Ok(state.shifts.iter().find(|s| s.id == id).cloned())
+    }
+
+    async fn get_shifts_by_driver_id(
+        &self,
+        driver_id: i32,
+        page: i32,
+        page_size: i32,
+    ) -> Result<Vec<DriverShift>, AppError> {
+        let state = self.state.lock().unwrap();
+        let mut shifts: Vec<DriverShift> = state
+            .shifts
+            .iter()
+            .filter(|s| s.driver_id == driver_id)
+            .cloned()
+            .collect();
+        shifts.sort_by_key(|s| Reverse((s.started_at, s.id)));
+        Ok(paginate(shifts, page, page_size))
+    }
+
+    async fn get_on_shift_drivers(&self, area_id: i32) -> Result<Vec<OnShiftDriver>, AppError> {
+        let state = self.state.lock().unwrap();
+        let mut drivers: Vec<OnShiftDriver> = state
+            .shifts
+            .iter()
+            .filter(|s| s.area_id == area_id && s.ended_at.is_none())
+            .filter_map(|s| {
+                let tow_truck = state.tow_trucks.iter().find(|t| t.id == s.tow_truck_id)?;
+                Some(OnShiftDriver {
+                    shift_id: s.id,
+                    tow_truck_id: s.tow_truck_id,
+                    driver_id: s.driver_id,
+                    driver_username: tow_truck.driver_username.clone().unwrap_or_default(),
+                    status: tow_truck.status.clone(),
+                    area_id: tow_truck.area_id,
+                    started_at: s.started_at,
+                })
+            })
+            .collect();
+        drivers.sort_by_key(|d| (d.started_at, d.shift_id));
+        Ok(drivers)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MapState {
+    // ノード ID とエリア ID の対応
+    pub node_areas: HashMap<i32, i32>,
+    // 辺と通行止めかどうか
+    pub edges: Vec<(Edge, bool)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryMapRepository {
+    pub state: Arc<Mutex<MapState>>,
+}
+
+impl InMemoryMapRepository {
+    pub fn new(node_areas: HashMap<i32, i32>, edges: Vec<Edge>) -> Self {
+        InMemoryMapRepository {
+            state: Arc::new(Mutex::new(MapState {
+                node_areas,
+                edges: edges.into_iter().map(|edge| (edge, false)).collect(),
+            })),
+        }
+    }
+
+    // 辺と通行止めかどうかを返す
+    pub fn edge(&self, node_a_id: i32, node_b_id: i32) -> Option<(Edge, bool)> {
+        let state = self.state.lock().unwrap();
+        state
+            .edges
+            .iter()
+            .find(|(edge, _)| connects(edge, node_a_id, node_b_id))
+            .cloned()
+    }
+}
+
+fn connects(edge: &Edge, node_a_id: i32, node_b_id: i32) -> bool {
+    (edge.node_a_id == node_a_id && edge.node_b_id == node_b_id)
+        || (edge.node_a_id == node_b_id && edge.node_b_id == node_a_id)
+}
+
+impl MapRepository for InMemoryMapRepository {
+    async fn get_all_area_ids(&self) -> Result<Vec<i32>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+        let area_ids: BTreeSet<i32> = state.node_areas.values().copied().collect();
+        Ok(area_ids.into_iter().collect())
+    }
+
+    async fn get_all_nodes(&self, area_id: Option<i32>) -> Result<Vec<Node>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .node_areas
+            .iter()
+            .filter(|(_, area)| area_id.is_none_or(|area_id| **area == area_id))
+            .map(|(id, _)| Node {
+                id: *id,
+                x: 0,
+                y: 0,
+            })
+            .collect())
+    }
+
+    async fn get_all_edges(&self, area_id: Option<i32>) -> Result<Vec<Edge>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .edges
+            .iter()
+            .filter(|(_, is_closed)| !is_closed)
+            .filter(|(edge, _)| {
+                area_id
+                    .is_none_or(|area_id| state.node_areas.get(&edge.node_a_id) == Some(&area_id))
+            })
+            .map(|(edge, _)| edge.clone())
+            .collect())
+    }
+
+    async fn get_area_id_by_node_id(&self, node_id: i32) -> Result<i32, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+        state
+            .node_areas
+            .get(&node_id)
+            .copied()
+            .ok_or(sqlx::Error::RowNotFound)
+    }
+
+    async fn get_area_ids_by_node_ids(
+        &self,
+        node_ids: &[i32],
+    ) -> Result<Vec<(i32, i32)>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+        Ok(node_ids
+            .iter()
+            .filter_map(|node_id| {
+                state
+                    .node_areas
+                    .get(node_id)
+                    .map(|area_id| (*node_id, *area_id))
+            })
+            .collect())
+    }
+
+    // 1 件でも存在しない辺があれば何も変更しない
+    async fn update_edges(&self, updates: &[EdgeUpdate]) -> Result<(), sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+        let mut edges = state.edges.clone();
+
+        for update in updates {
+            let (edge, is_closed) = edges
+                .iter_mut()
+                .find(|(e, _)| connects(e, update.node_a_id, update.node_b_id))
+                .ok_or(sqlx::Error::RowNotFound)?;
+            if let Some(weight) = update.weight {
+                edge.weight = weight;
+            }
+            if let Some(closed) = update.is_closed {
+                *is_closed = closed;
+            }
+        }
+
+        state.edges = edges;
+        Ok(())
+    }
+}
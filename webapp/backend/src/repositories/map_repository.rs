@@ -1,8 +1,9 @@
 use sqlx::MySqlPool;
+use tracing::instrument;
 
 use crate::{
     domains::map_service::MapRepository,
-    models::graph::{Edge, Node},
+    models::graph::{Edge, EdgeUpdate, Node},
 };
 
 #[derive(Debug)]
@@ -17,6 +18,16 @@ impl MapRepositoryImpl {
 }
 
 impl MapRepository for MapRepositoryImpl {
+    #[instrument(level = "debug", skip_all)]
+    async fn get_all_area_ids(&self) -> Result<Vec<i32>, sqlx::Error> {
+        let area_ids = sqlx::query_scalar("SELECT id FROM areas ORDER BY id")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(area_ids)
+    }
+
+    #[instrument(level = "debug", skip_all)]
     async fn get_all_nodes(&self, area_id: Option<i32>) -> Result<Vec<Node>, sqlx::Error> {
         let where_clause = match area_id {
             Some(_) => "WHERE area_id = ?",
@@ -51,10 +62,13 @@ impl MapRepository for MapRepositoryImpl {
         Ok(nodes)
     }
 
+    #[instrument(level = "debug", skip_all)]
     async fn get_all_edges(&self, area_id: Option<i32>) -> Result<Vec<Edge>, sqlx::Error> {
         let where_clause = match area_id {
-            Some(_) => "JOIN nodes n ON e.node_a_id = n.id WHERE n.area_id = ?",
-            None => "",
+            Some(_) => {
+                "JOIN nodes n ON e.node_a_id = n.id WHERE n.area_id = ? AND e.is_closed = FALSE"
+            }
+            None => "WHERE e.is_closed = FALSE",
         };
 
         let sql = format!(
@@ -85,6 +99,7 @@ impl MapRepository for MapRepositoryImpl {
         Ok(edges)
     }
 
+    #[instrument(level = "debug", skip_all)]
     async fn get_area_id_by_node_id(&self, node_id: i32) -> Result<i32, sqlx::Error> {
         let area_id = sqlx::query_scalar("SELECT area_id FROM nodes WHERE id = ?")
             .bind(node_id)
@@ -94,20 +109,52 @@ impl MapRepository for MapRepositoryImpl {
         Ok(area_id)
     }
 
-    async fn update_edge(
+    #[instrument(level = "debug", skip_all)]
+    async fn get_area_ids_by_node_ids(
         &self,
-        node_a_id: i32,
-        node_b_id: i32,
-        weight: i32,
-    ) -> Result<(), sqlx::Error> {
-        sqlx::query("UPDATE edges SET weight = ? WHERE (node_a_id = ? AND node_b_id = ?) OR (node_a_id = ? AND node_b_id = ?)")
-            .bind(weight)
-            .bind(node_a_id)
-            .bind(node_b_id)
-            .bind(node_b_id)
-            .bind(node_a_id)
-            .execute(&self.pool)
-            .await?;
+        node_ids: &[i32],
+    ) -> Result<Vec<(i32, i32)>, sqlx::Error> {
+        if node_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let sql = format!(
+            "SELECT id, area_id FROM nodes WHERE id IN ({})",
+            vec!["?"; node_ids.len()].join(", ")
+        );
+
+        let mut query = sqlx::query_as::<_, (i32, i32)>(&sql);
+        for node_id in node_ids {
+            query = query.bind(node_id);
+        }
+        let node_areas = query.fetch_all(&self.pool).await?;
+
+        Ok(node_areas)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn update_edges(&self, updates: &[EdgeUpdate]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        for update in updates {
+            let result = sqlx::query("UPDATE edges SET weight = COALESCE(?, weight), is_closed = COALESCE(?, is_closed) WHERE (node_a_id = ? AND node_b_id = ?) OR (node_a_id = ? AND node_b_id = ?)")
+                .bind(update.weight)
+                .bind(update.is_closed)
+                .bind(update.node_a_id)
+                .bind(update.node_b_id)
+                .bind(update.node_b_id)
+                .bind(update.node_a_id)
+                .execute(&mut tx)
+                .await?;
+
+            // 値が変わらない場合も一致した行数が返る (CLIENT_FOUND_ROWS)
+            if result.rows_affected() == 0 {
+                tx.rollback().await?;
+                return Err(sqlx::Error::RowNotFound);
+            }
+        }
+
+        tx.commit().await?;
 
         Ok(())
     }
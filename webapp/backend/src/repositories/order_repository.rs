@@ -3,6 +3,7 @@ use crate::errors::AppError;
 use crate::models::order::Order;
 use chrono::{DateTime, Utc};
 use sqlx::mysql::MySqlPool;
+use tracing::instrument;
 
 #[derive(Debug)]
 pub struct OrderRepositoryImpl {
@@ -16,6 +17,7 @@ impl OrderRepositoryImpl {
 }
 
 impl OrderRepository for OrderRepositoryImpl {
+    #[instrument(level = "debug", skip_all)]
     async fn find_order_by_id(&self, id: i32) -> Result<Order, AppError> {
         let order = sqlx::query_as::<_, Order>(
             "SELECT 
@@ -32,6 +34,7 @@ impl OrderRepository for OrderRepositoryImpl {
         Ok(order)
     }
 
+    #[instrument(level = "debug", skip_all)]
     async fn update_order_status(&self, order_id: i32, status: &str) -> Result<(), AppError> {
         sqlx::query("UPDATE orders SET status = ? WHERE id = ?")
             .bind(status)
@@ -42,6 +45,7 @@ impl OrderRepository for OrderRepositoryImpl {
         Ok(())
     }
 
+    #[instrument(level = "debug", skip_all)]
     async fn get_paginated_orders(
         &self,
         page: i32,
@@ -136,22 +140,24 @@ impl OrderRepository for OrderRepositoryImpl {
         Ok(orders)
     }
 
+    #[instrument(level = "debug", skip_all)]
     async fn create_order(
         &self,
         client_id: i32,
         node_id: i32,
         car_value: f64,
-    ) -> Result<(), AppError> {
-        sqlx::query("INSERT INTO orders (client_id, node_id, status, car_value) VALUES (?, ?, 'pending', ?)")
+    ) -> Result<i32, AppError> {
+        let result = sqlx::query("INSERT INTO orders (client_id, node_id, status, car_value) VALUES (?, ?, 'pending', ?)")
             .bind(client_id)
             .bind(node_id)
             .bind(car_value)
             .execute(&self.pool)
             .await?;
 
-        Ok(())
+        Ok(result.last_insert_id() as i32)
     }
 
+    #[instrument(level = "debug", skip_all)]
     async fn update_order_dispatched(
         &self,
         id: i32,
@@ -170,6 +176,139 @@ impl OrderRepository for OrderRepositoryImpl {
         Ok(())
     }
 
+    #[instrument(level = "debug", skip_all)]
+    async fn get_orders_by_client_id(
+        &self,
+        client_id: i32,
+        statuses: &[String],
+        page: i32,
+        page_size: i32,
+    ) -> Result<Vec<Order>, AppError> {
+        let status_clause = if statuses.is_empty() {
+            "".to_string()
+        } else {
+            format!("AND status IN ({})", vec!["?"; statuses.len()].join(", "))
+        };
+
+        let sql = format!(
+            "SELECT
+                *
+            FROM
+                orders
+            WHERE
+                client_id = ?
+            {}
+            ORDER BY
+                order_time DESC, id DESC
+            LIMIT ?
+            OFFSET ?",
+            status_clause
+        );
+
+        let mut query = sqlx::query_as::<_, Order>(&sql).bind(client_id);
+        for status in statuses {
+            query = query.bind(status);
+        }
+        let orders = query
+            .bind(page_size)
+            .bind(page * page_size)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(orders)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_active_order_by_tow_truck_id(
+        &self,
+        tow_truck_id: i32,
+    ) -> Result<Option<Order>, AppError> {
+        // 配車されてから完了するまでのオーダーは 1 台につき 1 件
+        let order = sqlx::query_as::<_, Order>(
+            "SELECT
+                *
+            FROM
+                orders
+            WHERE
+                tow_truck_id = ?
+            AND
+                status IN ('dispatched', 'arrived', 'picked_up')
+            ORDER BY
+                order_time DESC, id DESC
+            LIMIT 1",
+        )
+        .bind(tow_truck_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(order)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_orders_by_tow_truck_id(
+        &self,
+        tow_truck_id: i32,
+        page: i32,
+        page_size: i32,
+    ) -> Result<Vec<Order>, AppError> {
+        let orders = sqlx::query_as::<_, Order>(
+            "SELECT
+                *
+            FROM
+                orders
+            WHERE
+                tow_truck_id = ?
+            ORDER BY
+                order_time DESC, id DESC
+            LIMIT ?
+            OFFSET ?",
+        )
+        .bind(tow_truck_id)
+        .bind(page_size)
+        .bind(page * page_size)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(orders)
+    }
+
+    // 同時に報告された場合でも先に更新した方だけが成功するよう、現在のステータスを条件に更新する
+    #[instrument(level = "debug", skip_all)]
+    async fn advance_order_status(
+        &self,
+        order_id: i32,
+        current_status: &str,
+        next_status: &str,
+    ) -> Result<bool, AppError> {
+        let result = sqlx::query("UPDATE orders SET status = ? WHERE id = ? AND status = ?")
+            .bind(next_status)
+            .bind(order_id)
+            .bind(current_status)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // 積み込み済みのオーダーだけを完了にする
+    #[instrument(level = "debug", skip_all)]
+    async fn update_order_completed(
+        &self,
+        order_id: i32,
+        completed_time: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            "UPDATE orders SET status = 'completed', completed_time = ? WHERE id = ? AND status = 'picked_up'",
+        )
+        .bind(completed_time)
+        .bind(order_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[instrument(level = "debug", skip_all)]
     async fn create_completed_order(
         &self,
         order_id: i32,
@@ -1,7 +1,9 @@
 use crate::domains::tow_truck_service::TowTruckRepository;
 use crate::errors::AppError;
-use crate::models::tow_truck::TowTruck;
+use crate::models::tow_truck::{DriverShift, OnShiftDriver, TowTruck};
+use chrono::{DateTime, Utc};
 use sqlx::mysql::MySqlPool;
+use tracing::instrument;
 
 #[derive(Debug)]
 pub struct TowTruckRepositoryImpl {
@@ -15,6 +17,7 @@ impl TowTruckRepositoryImpl {
 }
 
 impl TowTruckRepository for TowTruckRepositoryImpl {
+    #[instrument(level = "debug", skip_all)]
     async fn get_paginated_tow_trucks(
         &self,
         page: i32,
@@ -80,6 +83,7 @@ impl TowTruckRepository for TowTruckRepositoryImpl {
         Ok(tow_trucks)
     }
 
+    #[instrument(level = "debug", skip_all)]
     async fn update_location(&self, tow_truck_id: i32, node_id: i32) -> Result<(), AppError> {
         sqlx::query("INSERT INTO locations (tow_truck_id, node_id) VALUES (?, ?)")
             .bind(tow_truck_id)
@@ -89,6 +93,7 @@ impl TowTruckRepository for TowTruckRepositoryImpl {
         Ok(())
     }
 
+    #[instrument(level = "debug", skip_all)]
     async fn update_status(&self, tow_truck_id: i32, status: &str) -> Result<(), AppError> {
         sqlx::query("UPDATE tow_trucks SET status = ? WHERE id = ?")
             .bind(status)
@@ -99,6 +104,7 @@ impl TowTruckRepository for TowTruckRepositoryImpl {
         Ok(())
     }
 
+    #[instrument(level = "debug", skip_all)]
     async fn find_tow_truck_by_id(&self, id: i32) -> Result<Option<TowTruck>, AppError> {
         let tow_truck = sqlx::query_as::<_, TowTruck>(
             "SELECT
@@ -124,4 +130,203 @@ impl TowTruckRepository for TowTruckRepositoryImpl {
 
         Ok(tow_truck)
     }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_tow_truck_by_driver_id(
+        &self,
+        driver_id: i32,
+    ) -> Result<Option<TowTruck>, AppError> {
+        let tow_truck = sqlx::query_as::<_, TowTruck>(
+            "SELECT
+                tt.id, tt.driver_id, u.username AS driver_username, tt.status, l.node_id, tt.area_id
+            FROM
+                tow_trucks tt
+            JOIN
+                users u
+            ON
+                tt.driver_id = u.id
+            JOIN
+                locations l
+            ON
+                tt.id = l.tow_truck_id
+            WHERE
+                tt.driver_id = ?
+            AND
+                l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)",
+        )
+        .bind(driver_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(tow_truck)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn start_shift(
+        &self,
+        tow_truck_id: i32,
+        driver_id: i32,
+        area_id: i32,
+        started_at: DateTime<Utc>,
+    ) -> Result<i32, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        // 勤務中のシフトが既にある場合は一意制約違反 (Conflict) になる
+        let result = sqlx::query(
+            "INSERT INTO driver_shifts (tow_truck_id, driver_id, area_id, started_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(tow_truck_id)
+        .bind(driver_id)
+        .bind(area_id)
+        .bind(started_at)
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            "UPDATE tow_trucks SET status = 'available' WHERE id = ? AND status = 'off_duty'",
+        )
+        .bind(tow_truck_id)
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(result.last_insert_id() as i32)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn end_shift(
+        &self,
+        shift_id: i32,
+        tow_truck_id: i32,
+        ended_at: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let shift =
+            sqlx::query("UPDATE driver_shifts SET ended_at = ? WHERE id = ? AND ended_at IS NULL")
+                .bind(ended_at)
+                .bind(shift_id)
+                .execute(&mut tx)
+                .await?;
+
+        // 配車中のレッカー車は勤務を終了できない
+        let tow_truck = sqlx::query(
+            "UPDATE tow_trucks SET status = 'off_duty' WHERE id = ? AND status <> 'busy'",
+        )
+        .bind(tow_truck_id)
+        .execute(&mut tx)
+        .await?;
+
+        if shift.rows_affected() != 1 || tow_truck.rows_affected() != 1 {
+            tx.rollback().await?;
+            return Ok(false);
+        }
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_open_shift_by_driver_id(
+        &self,
+        driver_id: i32,
+    ) -> Result<Option<DriverShift>, AppError> {
+        let shift = sqlx::query_as::<_, DriverShift>(
+            "SELECT
+                id, tow_truck_id, driver_id, area_id, started_at, ended_at
+            FROM
+                driver_shifts
+            WHERE
+                driver_id = ?
+            AND
+                ended_at IS NULL",
+        )
+        .bind(driver_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(shift)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_shift_by_id(&self, id: i32) -> Result<Option<DriverShift>, AppError> {
+        let shift = sqlx::query_as::<_, DriverShift>(
+            "SELECT
+                id, tow_truck_id, driver_id, area_id, started_at, ended_at
+            FROM
+                driver_shifts
+            WHERE
+                id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(shift)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_shifts_by_driver_id(
+        &self,
+        driver_id: i32,
+        page: i32,
+        page_size: i32,
+    ) -> Result<Vec<DriverShift>, AppError> {
+        let shifts = sqlx::query_as::<_, DriverShift>(
+            "SELECT
+                id, tow_truck_id, driver_id, area_id, started_at, ended_at
+            FROM
+                driver_shifts
+            WHERE
+                driver_id = ?
+            ORDER BY
+                started_at DESC, id DESC
+            LIMIT ?
+            OFFSET ?",
+        )
+        .bind(driver_id)
+        .bind(page_size)
+        .bind(page * page_size)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(shifts)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_on_shift_drivers(&self, area_id: i32) -> Result<Vec<OnShiftDriver>, AppError> {
+        let drivers = sqlx::query_as::<_, OnShiftDriver>(
+            "SELECT
+                ds.id AS shift_id,
+                ds.tow_truck_id,
+                ds.driver_id,
+                u.username AS driver_username,
+                tt.status,
+                tt.area_id,
+                ds.started_at
+            FROM
+                driver_shifts ds
+            JOIN
+                tow_trucks tt
+            ON
+                ds.tow_truck_id = tt.id
+            JOIN
+                users u
+            ON
+                ds.driver_id = u.id
+            WHERE
+                tt.area_id = ?
+            AND
+                ds.ended_at IS NULL
+            ORDER BY
+                ds.started_at ASC, ds.id ASC",
+        )
+        .bind(area_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(drivers)
+    }
 }
@@ -1,14 +1,24 @@
+use actix_web::{web, HttpRequest};
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Argon2,
 };
 use rand::Rng;
+use std::net::IpAddr;
 
 use crate::errors::AppError;
 
 pub fn generate_session_token() -> String {
+    generate_random_token(30)
+}
+
+pub fn generate_password_reset_token() -> String {
+    generate_random_token(48)
+}
+
+fn generate_random_token(length: usize) -> String {
     let mut rng = rand::thread_rng();
-    let token: String = (0..30)
+    let token: String = (0..length)
         .map(|_| {
             let idx = rng.gen_range(0..62);
             let chars = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
@@ -43,3 +53,152 @@ pub fn verify_password(hashed_password: &str, input_password: &str) -> Result<bo
         Err(_) => Ok(false),
     }
 }
+
+// リバースプロキシのアドレス (IP または CIDR) の一覧
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    networks: Vec<(IpAddr, u8)>,
+}
+
+impl TrustedProxies {
+    pub fn parse(entries: &[String]) -> Result<Self, String> {
+        let networks = entries
+            .iter()
+            .map(|entry| parse_network(entry).ok_or_else(|| entry.clone()))
+            .collect::<Result<_, _>>()?;
+        Ok(TrustedProxies { networks })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.networks
+            .iter()
+            .any(|(network, prefix_len)| in_network(ip, *network, *prefix_len))
+    }
+}
+
+fn parse_network(entry: &str) -> Option<(IpAddr, u8)> {
+    let (addr, prefix_len) = match entry.split_once('/') {
+        Some((addr, prefix_len)) => (addr.parse::<IpAddr>().ok()?, Some(prefix_len.parse().ok()?)),
+        None => (entry.parse::<IpAddr>().ok()?, None),
+    };
+    let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+    match prefix_len {
+        Some(prefix_len) if prefix_len > max_prefix_len => None,
+        Some(prefix_len) => Some((addr, prefix_len)),
+        None => Some((addr, max_prefix_len)),
+    }
+}
+
+fn in_network(ip: IpAddr, network: IpAddr, prefix_len: u8) -> bool {
+    // IPv4 射影アドレス (::ffff:a.b.c.d) で接続された場合も IPv4 として比較する
+    let ip = match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        IpAddr::V4(_) => ip,
+    };
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+// 接続元が信頼するプロキシの場合に限り、プロキシが設定した X-Real-IP をクライアントの IP とする
+// (X-Forwarded-For はクライアントが自由に付与できるため使わない)
+pub fn client_ip(req: &HttpRequest) -> Option<String> {
+    let peer_ip = req.peer_addr()?.ip();
+
+    let from_trusted_proxy = req
+        .app_data::<web::Data<TrustedProxies>>()
+        .is_some_and(|trusted_proxies| trusted_proxies.contains(peer_ip));
+    if from_trusted_proxy {
+        let real_ip = req
+            .headers()
+            .get("X-Real-IP")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<IpAddr>().ok());
+        if let Some(real_ip) = real_ip {
+            return Some(real_ip.to_string());
+        }
+    }
+
+    Some(peer_ip.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    fn request(peer_addr: &str, trusted_proxies: &[&str]) -> TestRequest {
+        let trusted_proxies: Vec<String> = trusted_proxies.iter().map(|s| s.to_string()).collect();
+        TestRequest::default()
+            .peer_addr(peer_addr.parse::<SocketAddr>().unwrap())
+            .app_data(web::Data::new(
+                TrustedProxies::parse(&trusted_proxies).unwrap(),
+            ))
+    }
+
+    #[test]
+    fn client_ip_uses_real_ip_from_trusted_proxy() {
+        let req = request("172.18.0.5:50000", &["172.16.0.0/12"])
+            .insert_header(("X-Real-IP", "203.0.113.7"))
+            .to_http_request();
+
+        assert_eq!(client_ip(&req).as_deref(), Some("203.0.113.7"));
+    }
+
+    #[test]
+    fn client_ip_ignores_headers_from_untrusted_peer() {
+        let req = request("198.51.100.9:50000", &["172.16.0.0/12"])
+            .insert_header(("X-Real-IP", "203.0.113.7"))
+            .insert_header(("X-Forwarded-For", "203.0.113.8"))
+            .to_http_request();
+
+        assert_eq!(client_ip(&req).as_deref(), Some("198.51.100.9"));
+    }
+
+    #[test]
+    fn client_ip_ignores_forwarded_for_from_trusted_proxy() {
+        let req = request("172.18.0.5:50000", &["172.18.0.5"])
+            .insert_header(("X-Forwarded-For", "203.0.113.8"))
+            .to_http_request();
+
+        assert_eq!(client_ip(&req).as_deref(), Some("172.18.0.5"));
+    }
+
+    #[test]
+    fn trusted_proxies_match_cidr_ranges() {
+        let trusted_proxies = TrustedProxies::parse(&[
+            "10.0.0.0/8".to_string(),
+            "192.168.1.10".to_string(),
+            "fd00::/8".to_string(),
+        ])
+        .unwrap();
+
+        assert!(trusted_proxies.contains("10.255.0.1".parse().unwrap()));
+        assert!(trusted_proxies.contains("192.168.1.10".parse().unwrap()));
+        assert!(trusted_proxies.contains("::ffff:10.0.0.1".parse().unwrap()));
+        assert!(trusted_proxies.contains("fd12::1".parse().unwrap()));
+        assert!(!trusted_proxies.contains("11.0.0.1".parse().unwrap()));
+        assert!(!trusted_proxies.contains("192.168.1.11".parse().unwrap()));
+    }
+
+    #[test]
+    fn trusted_proxies_reject_invalid_entries() {
+        for entry in ["nginx", "10.0.0.0/33", "10.0.0.0/", "::/129"] {
+            assert_eq!(
+                TrustedProxies::parse(&[entry.to_string()]).unwrap_err(),
+                entry
+            );
+        }
+    }
+}